@@ -0,0 +1,111 @@
+//! Secure read path: decrypting packets in place with [`LocoSecureStream`], against
+//! decrypting each packet into a buffer of its own with [`LocoClientSecureLayer`]
+//! as the stream did before.
+//!
+//! Run with `cargo bench --bench secure_read`.
+
+mod util;
+
+use std::{
+    io::ErrorKind,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_io::AsyncRead;
+use futures_loco_protocol::{
+    loco_protocol::secure::{client::LocoClientSecureLayer, SecurePacket},
+    secure::{
+        encode_data_frame,
+        rsa::{BigUint, RsaPublicKey},
+        LocoSecureStream,
+    },
+};
+use util::{measure, noop_waker, ChunkedReader};
+
+const KEY: [u8; 16] = [7; 16];
+const INNER_CHUNK_SIZE: usize = 16 * 1024;
+
+fn frames(count: usize, data_size: usize) -> Vec<u8> {
+    let data = vec![0x5a; data_size];
+
+    (0..count)
+        .flat_map(|i| {
+            encode_data_frame(
+                KEY,
+                SecurePacket {
+                    iv: [i as u8; 16],
+                    data: &data,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Decrypt every frame of `input` through [`LocoSecureStream`], returning the plaintext size
+fn read_in_place(input: &[u8]) -> usize {
+    // Never used for reading, any well formed key will do
+    let rsa_key =
+        RsaPublicKey::new(BigUint::from_bytes_be(&[0xff; 128]), 65537_u32.into()).unwrap();
+    let mut stream = LocoSecureStream::new(rsa_key, ChunkedReader::new(input, INNER_CHUNK_SIZE));
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut buf = [0; 64 * 1024];
+
+    let mut total = 0;
+    loop {
+        match Pin::new(&mut stream).poll_read(&mut cx, &mut buf) {
+            Poll::Ready(Ok(read)) => total += read,
+
+            // End of input between packets
+            Poll::Ready(Err(err)) if err.kind() == ErrorKind::UnexpectedEof => break total,
+
+            poll => panic!("unexpected read result {poll:?}"),
+        }
+    }
+}
+
+/// Decrypt every frame of `input` the way [`LocoSecureStream`] did before, copying each
+/// chunk into the layer and each packet into its own allocation
+fn read_with_layer(input: &[u8]) -> usize {
+    let mut layer = LocoClientSecureLayer::new(KEY);
+    let mut buf = [0; 64 * 1024];
+
+    let mut total = 0;
+    for chunk in input.chunks(INNER_CHUNK_SIZE) {
+        layer.read_buffer.extend(chunk);
+
+        while let Some(packet) = layer.read() {
+            for part in packet.data.chunks(buf.len()) {
+                buf[..part.len()].copy_from_slice(part);
+                total += part.len();
+            }
+        }
+    }
+
+    total
+}
+
+fn main() {
+    for (count, data_size) in [(4096, 64), (1024, 1024), (64, 64 * 1024)] {
+        let input = frames(count, data_size);
+        assert_eq!(read_in_place(&input), count * data_size);
+        assert_eq!(read_with_layer(&input), count * data_size);
+
+        measure(
+            &format!("in place, {count} packets of {data_size} bytes"),
+            50,
+            || {
+                std::hint::black_box(read_in_place(&input));
+            },
+        );
+        measure(
+            &format!("separate buffers, {count} packets of {data_size} bytes"),
+            50,
+            || {
+                std::hint::black_box(read_with_layer(&input));
+            },
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Bursts of small sends: queueing commands with [`LocoClient::write`] and writing them
+//! out, against encoding each with [`LocoSink::send`] and writing only the front slice
+//! of the ring buffer per inner write as the client did before. Also measures
+//! [`VecDeque::make_contiguous`] on its own, on buffers wrapped by rotating them,
+//! up to the 64 KiB limit below which the client coalesces wrapped writes.
+//!
+//! Run with `cargo bench --bench small_sends`.
+
+mod util;
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_io::AsyncWrite;
+use futures_loco_protocol::{
+    loco_protocol::command::{client::LocoSink, Command, Header, Method},
+    LocoClient,
+};
+use util::{measure, noop_waker, LimitedWriter};
+
+const BURSTS: usize = 256;
+const DATA: [u8; 16] = [0x5a; 16];
+
+/// Size of an encoded command, a 22 byte header followed by [`DATA`]
+const COMMAND_SIZE: usize = 22 + DATA.len();
+
+/// Bytes kept buffered between bursts, so the queued bytes move around the ring buffer
+const LEFTOVER: usize = 1000;
+
+/// Queue `burst` commands at a time through [`LocoClient`], writing all but
+/// [`LEFTOVER`] bytes after each burst. Returns the number of inner writes.
+fn client_bursts(burst: usize) -> usize {
+    let burst_size = burst * COMMAND_SIZE;
+    let mut client =
+        LocoClient::new(LimitedWriter::new(burst_size)).with_max_total_buffer(LEFTOVER + 1);
+    let method = Method::new("MSG").unwrap();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    for _ in 0..BURSTS {
+        for _ in 0..burst {
+            Pin::new(&mut client).write(method.clone(), &DATA);
+        }
+
+        assert!(matches!(
+            Pin::new(&mut client).poll_writable(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+    }
+
+    client.inner().writes
+}
+
+/// Same bursts as [`client_bursts`] through a bare [`LocoSink`], without coalescing
+fn sink_bursts(burst: usize) -> usize {
+    let burst_size = burst * COMMAND_SIZE;
+    let mut writer = LimitedWriter::new(burst_size);
+    let mut sink = LocoSink::new();
+    let method = Method::new("MSG").unwrap();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    for id in 0..BURSTS * burst {
+        sink.send(Command {
+            header: Header {
+                id: id as u32,
+                status: 0,
+                method: method.clone(),
+                data_type: 0,
+            },
+            data: &DATA,
+        });
+
+        if (id + 1) % burst != 0 {
+            continue;
+        }
+
+        while sink.write_buffer.len() > LEFTOVER {
+            let Poll::Ready(Ok(written)) =
+                Pin::new(&mut writer).poll_write(&mut cx, sink.write_buffer.as_slices().0)
+            else {
+                unreachable!()
+            };
+            sink.write_buffer.drain(..written);
+        }
+    }
+
+    writer.writes
+}
+
+/// Full ring buffer of at least `len` bytes, which [`VecDeque::rotate_left`] wraps
+fn full_buffer(len: usize) -> VecDeque<u8> {
+    let mut buffer = VecDeque::with_capacity(len);
+    buffer.resize(buffer.capacity(), 0x5a);
+
+    buffer
+}
+
+fn main() {
+    for burst in [4, 32, 256] {
+        println!(
+            "{burst} commands per burst: {} inner writes coalesced, {} writes of front slices",
+            client_bursts(burst),
+            sink_bursts(burst)
+        );
+
+        measure(&format!("client, bursts of {burst} sends"), 200, || {
+            std::hint::black_box(client_bursts(burst));
+        });
+        measure(&format!("bare sink, bursts of {burst} sends"), 200, || {
+            std::hint::black_box(sink_bursts(burst));
+        });
+    }
+
+    for len in [1024, 16 * 1024, 64 * 1024] {
+        let mut buffer = full_buffer(len);
+        buffer.rotate_left(len / 2);
+        assert!(!buffer.as_slices().1.is_empty());
+
+        measure(&format!("rotate, {len} bytes"), 1000, || {
+            buffer.rotate_left(len / 2);
+            std::hint::black_box(buffer.as_slices());
+        });
+        measure(
+            &format!("rotate and make_contiguous, {len} bytes"),
+            1000,
+            || {
+                buffer.rotate_left(len / 2);
+                std::hint::black_box(buffer.make_contiguous());
+            },
+        );
+    }
+}
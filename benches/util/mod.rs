@@ -0,0 +1,94 @@
+// Each benchmark uses only some of these
+#![allow(dead_code)]
+
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    time::Instant,
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+/// Run `f` `iterations` times after a warm up run, printing the mean time of a run
+pub fn measure(name: &str, iterations: u32, mut f: impl FnMut()) {
+    f();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+
+    println!("{name:<48} {:>12.2?}", start.elapsed() / iterations);
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+pub fn noop_waker() -> Waker {
+    Waker::from(Arc::new(NoopWaker))
+}
+
+/// Reader returning `data` in chunks of at most `chunk_size` bytes, then EOF
+#[derive(Debug, Clone)]
+pub struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl<'a> ChunkedReader<'a> {
+    pub const fn new(data: &'a [u8], chunk_size: usize) -> Self {
+        Self { data, chunk_size }
+    }
+}
+
+impl AsyncRead for ChunkedReader<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = self.data.len().min(buf.len()).min(self.chunk_size);
+        buf[..read].copy_from_slice(&self.data[..read]);
+        self.data = &self.data[read..];
+
+        Poll::Ready(Ok(read))
+    }
+}
+
+/// Writer accepting at most `limit` bytes per write, counting the writes
+#[derive(Debug, Clone)]
+pub struct LimitedWriter {
+    limit: usize,
+    pub writes: usize,
+}
+
+impl LimitedWriter {
+    pub const fn new(limit: usize) -> Self {
+        Self { limit, writes: 0 }
+    }
+}
+
+impl AsyncWrite for LimitedWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.writes += 1;
+
+        Poll::Ready(Ok(buf.len().min(self.limit)))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    future::{poll_fn, Future},
+    io::{self, ErrorKind},
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+};
+
+use flume::{Receiver, Sender};
+use futures_core::Stream;
+use futures_io::{AsyncRead, AsyncWrite};
+use loco_protocol::command::{BoxedCommand, Method};
+
+use crate::{
+    close::CloseSignal,
+    error::LocoError,
+    session::{Error, LocoSession, LocoSessionStream},
+    LocoClient,
+};
+
+pub type ActorFuture = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+type Subscribers = Arc<Mutex<Vec<Sender<BoxedCommand>>>>;
+
+/// Task owning a connection, used from other tasks through [`ClientHandle`]s.
+///
+/// [`Self::run`] drives both directions of the connection. Requests are answered through the
+/// handle making them and every other command is forwarded to each [`ClientHandle::subscribe`]r.
+pub struct ClientActor<T: Clone> {
+    stream: LocoSessionStream<T>,
+    subscribers: Subscribers,
+    close: CloseSignal,
+}
+
+impl<T: AsyncRead + AsyncWrite + Clone> ClientActor<T> {
+    /// Wrap `client`, to be ended by [`ClientHandle::shutdown`]. A close signal the client
+    /// already has still ends the actor, but is not closed by the shutdown.
+    pub fn new(client: LocoClient<T>) -> (Self, ClientHandle) {
+        let close = client
+            .config
+            .close_signal
+            .as_ref()
+            .map_or_else(CloseSignal::new, CloseSignal::child);
+        let (session, stream) = LocoSession::new(client.with_close_signal(close.clone()));
+        let subscribers = Subscribers::default();
+
+        (
+            Self {
+                stream,
+                subscribers: subscribers.clone(),
+                close: close.clone(),
+            },
+            ClientHandle {
+                session,
+                subscribers,
+                close,
+            },
+        )
+    }
+
+    /// Create an actor and hand its [`Self::run`] future to `spawn`,
+    /// such as `tokio::spawn` or `smol::spawn` wrapped in a closure
+    pub fn spawn(client: LocoClient<T>, spawn: impl FnOnce(ActorFuture)) -> ClientHandle
+    where
+        T: Send + 'static,
+    {
+        let (actor, handle) = Self::new(client);
+        spawn(Box::pin(actor.run()));
+
+        handle
+    }
+
+    /// Drive the connection until it ends or fails. Returns `Ok` once the stream
+    /// ended or [`ClientHandle::shutdown`] was called, leaving the connection to be dropped.
+    pub async fn run(self) -> io::Result<()> {
+        let mut stream = pin!(self.stream);
+
+        while let Some(read) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            match read {
+                Ok(command) => broadcast(&self.subscribers, command),
+
+                // Peer ended the stream between commands, rather than within a truncated one
+                Err(err)
+                    if err.kind() == ErrorKind::UnexpectedEof
+                        && LocoError::from_io(&err).is_none() =>
+                {
+                    break
+                }
+
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.close.close();
+        Ok(())
+    }
+}
+
+fn broadcast(subscribers: &Mutex<Vec<Sender<BoxedCommand>>>, command: BoxedCommand) {
+    let mut subscribers = subscribers.lock().unwrap();
+    subscribers.retain(|subscriber| subscriber.send(command.clone()).is_ok());
+}
+
+/// Handle of a [`ClientActor`], cloned for each task using the connection
+#[derive(Debug, Clone)]
+pub struct ClientHandle {
+    session: LocoSession,
+    subscribers: Subscribers,
+    close: CloseSignal,
+}
+
+impl ClientHandle {
+    /// Send a request and wait for its response
+    pub async fn request(&self, method: Method, data: Vec<u8>) -> Result<BoxedCommand, Error> {
+        self.session.request(method, data).await?.await
+    }
+
+    /// Send a one-way command
+    pub async fn send(&self, method: Method, data: Vec<u8>) -> Result<(), Error> {
+        self.session.notify(method, data).await
+    }
+
+    /// Receive every command other than responses read from now on.
+    /// Commands are queued unbounded until received, so keep receiving or drop the receiver.
+    pub fn subscribe(&self) -> Receiver<BoxedCommand> {
+        let (sender, receiver) = flume::unbounded();
+        self.subscribers.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    /// Session used by this handle, for requests with pushes or push waiters
+    pub fn session(&self) -> &LocoSession {
+        &self.session
+    }
+
+    /// End the actor, once it notices unless it already has
+    pub fn shutdown(&self) {
+        self.close.close();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.close.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::{pin, Pin},
+        task::{Context, Poll},
+    };
+
+    use loco_protocol::command::Method;
+
+    use super::ClientActor;
+    use crate::{
+        close::CloseSignal,
+        frame::encode_command,
+        test_util::{noop_waker, ReadStep, ScriptedTransport},
+        LocoClient,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    fn poll<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+        let waker = noop_waker();
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn requests_are_answered_through_the_handle() {
+        // Polls writing a request read twice
+        let transport = ScriptedTransport::new([ReadStep::Pending, ReadStep::Pending]);
+        let (actor, handle) = ClientActor::new(LocoClient::new(transport.clone()));
+        let mut run = pin!(actor.run());
+
+        let mut request = pin!(handle.request(method("GETMEM"), b"request".to_vec()));
+        assert!(poll(request.as_mut()).is_pending());
+        assert!(poll(run.as_mut()).is_pending());
+        assert_eq!(
+            transport.written(),
+            encode_command(1, method("GETMEM"), b"request")
+        );
+
+        transport.push(ReadStep::Data(encode_command(
+            1,
+            method("GETMEM"),
+            b"response",
+        )));
+        transport.push(ReadStep::Pending);
+        assert!(poll(run.as_mut()).is_pending());
+
+        let Poll::Ready(Ok(response)) = poll(request.as_mut()) else {
+            panic!("response did not reach the handle");
+        };
+        assert_eq!(&*response.data, b"response");
+    }
+
+    #[test]
+    fn pushes_reach_subscribers() {
+        let transport = ScriptedTransport::new([
+            ReadStep::Data(encode_command(0, method("MSG"), b"push")),
+            ReadStep::Pending,
+        ]);
+        let (actor, handle) = ClientActor::new(LocoClient::new(transport));
+        let mut run = pin!(actor.run());
+
+        let pushes = handle.subscribe();
+        assert!(poll(run.as_mut()).is_pending());
+
+        let push = pushes.try_recv().unwrap();
+        assert_eq!(
+            (push.header.method, &*push.data),
+            (method("MSG"), &b"push"[..])
+        );
+        assert!(pushes.try_recv().is_err());
+    }
+
+    #[test]
+    fn shutdown_ends_run() {
+        let transport = ScriptedTransport::new([ReadStep::Pending]);
+        let (actor, handle) = ClientActor::new(LocoClient::new(transport));
+        let mut run = pin!(actor.run());
+
+        assert!(poll(run.as_mut()).is_pending());
+        handle.shutdown();
+
+        assert!(matches!(poll(run.as_mut()), Poll::Ready(Ok(()))));
+        assert!(handle.is_shutdown());
+    }
+
+    #[test]
+    fn earlier_close_signal_still_ends_run() {
+        let signal = CloseSignal::new();
+        let client = LocoClient::new(ScriptedTransport::new([ReadStep::Pending]))
+            .with_close_signal(signal.clone());
+        let (actor, handle) = ClientActor::new(client);
+        let mut run = pin!(actor.run());
+        assert!(poll(run.as_mut()).is_pending());
+
+        signal.close();
+        assert!(matches!(poll(run.as_mut()), Poll::Ready(Ok(()))));
+        assert!(handle.is_shutdown());
+    }
+
+    #[test]
+    fn shutdown_leaves_earlier_close_signal_open() {
+        let signal = CloseSignal::new();
+        let client = LocoClient::new(ScriptedTransport::new([ReadStep::Pending]))
+            .with_close_signal(signal.clone());
+        let (actor, handle) = ClientActor::new(client);
+        let mut run = pin!(actor.run());
+
+        handle.shutdown();
+        assert!(matches!(poll(run.as_mut()), Poll::Ready(Ok(()))));
+        assert!(!signal.is_closed());
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressureEvent {
+    /// Buffered write bytes reached the high watermark
+    High { buffered: usize },
+
+    /// Buffered write bytes dropped to the low watermark after a [`BackpressureEvent::High`]
+    Low { buffered: usize },
+}
+
+pub type BackpressureCallback = Arc<dyn Fn(BackpressureEvent) + Send + Sync>;
+
+/// Watermarks and callback set with [`crate::LocoClient::with_backpressure`]
+#[derive(Clone)]
+pub(crate) struct Backpressure {
+    high: usize,
+    low: usize,
+    callback: BackpressureCallback,
+
+    raised: bool,
+}
+
+impl Backpressure {
+    pub(crate) fn new(high: usize, low: usize, callback: BackpressureCallback) -> Self {
+        Self {
+            high,
+            low: low.min(high),
+            callback,
+
+            raised: false,
+        }
+    }
+
+    /// Call back if `buffered` crossed a watermark since the last update
+    pub(crate) fn update(&mut self, buffered: usize) {
+        if !self.raised && buffered >= self.high {
+            self.raised = true;
+            (self.callback)(BackpressureEvent::High { buffered });
+        } else if self.raised && buffered <= self.low {
+            self.raised = false;
+            (self.callback)(BackpressureEvent::Low { buffered });
+        }
+    }
+}
+
+impl Debug for Backpressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backpressure")
+            .field("high", &self.high)
+            .field("low", &self.low)
+            .field("raised", &self.raised)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Backpressure, BackpressureEvent};
+
+    #[test]
+    fn events_fire_once_per_crossing() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut backpressure = Backpressure::new(100, 20, {
+            let events = events.clone();
+            Arc::new(move |event| events.lock().unwrap().push(event))
+        });
+
+        for buffered in [50, 100, 150, 120, 60, 21, 20, 10, 50, 99, 100] {
+            backpressure.update(buffered);
+        }
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                BackpressureEvent::High { buffered: 100 },
+                BackpressureEvent::Low { buffered: 20 },
+                BackpressureEvent::High { buffered: 100 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub type ClockSkewCallback = Arc<dyn Fn(ClockSkew) + Send + Sync>;
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Future resolving once [`Clock::now`] reaches `deadline`
+    fn sleep_until(&self, deadline: Instant) -> Sleep;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(futures_timer::Delay::new(
+            deadline.saturating_duration_since(Instant::now()),
+        ))
+    }
+}
+
+/// Clock reading which went backwards from an earlier reading it was measured against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkew {
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl ClockSkew {
+    /// How far `end` is behind `start`
+    pub fn behind(&self) -> Duration {
+        self.start.saturating_duration_since(self.end)
+    }
+}
+
+/// Time elapsed from `start` to `end`, or the [`ClockSkew`] if `end` is earlier.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use futures_loco_protocol::clock::{elapsed, Clock, ManualClock};
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+///
+/// clock.advance(Duration::from_millis(5));
+/// assert_eq!(elapsed(start, clock.now()), Ok(Duration::from_millis(5)));
+///
+/// let later = start + Duration::from_secs(1);
+/// clock.set(start);
+/// assert_eq!(elapsed(later, clock.now()).unwrap_err().behind(), Duration::from_secs(1));
+/// ```
+pub fn elapsed(start: Instant, end: Instant) -> Result<Duration, ClockSkew> {
+    end.checked_duration_since(start)
+        .ok_or(ClockSkew { start, end })
+}
+
+/// Measure time elapsed since `start`, clamping to zero and reporting skewed readings
+pub(crate) fn elapsed_since(
+    clock: &dyn Clock,
+    start: Instant,
+    on_skew: Option<&SkewReporter>,
+) -> Duration {
+    elapsed(start, clock.now()).unwrap_or_else(|skew| {
+        if let Some(SkewReporter(callback)) = on_skew {
+            callback(skew);
+        }
+
+        Duration::ZERO
+    })
+}
+
+#[derive(Clone)]
+pub(crate) struct SkewReporter(pub ClockSkewCallback);
+
+impl Debug for SkewReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SkewReporter").finish_non_exhaustive()
+    }
+}
+
+/// Clock which only moves when told to, for deterministic timing tests
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    state: Arc<Mutex<ManualState>>,
+}
+
+#[derive(Debug)]
+struct ManualState {
+    now: Instant,
+    sleepers: Vec<Waker>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(instant: Instant) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ManualState {
+                now: instant,
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += duration;
+
+        state.sleepers.drain(..).for_each(Waker::wake);
+    }
+
+    pub fn set(&self, instant: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.now = instant;
+
+        state.sleepers.drain(..).for_each(Waker::wake);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        let state = self.state.clone();
+
+        Box::pin(std::future::poll_fn(move |cx| {
+            let mut state = state.lock().unwrap();
+
+            if state.now >= deadline {
+                Poll::Ready(())
+            } else {
+                state.sleepers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    };
+
+    use loco_protocol::command::Method;
+
+    use super::ManualClock;
+    use crate::{
+        frame::encode_command,
+        test_util::{noop_waker, ReadStep, ScriptedTransport},
+        LocoClient,
+    };
+
+    #[test]
+    fn skewed_round_trip_is_zero_and_reported() {
+        let earlier = Instant::now();
+        let clock = ManualClock::starting_at(earlier + Duration::from_secs(1));
+        let skews = Arc::new(Mutex::new(Vec::new()));
+
+        let method = Method::new("GETMEM").unwrap();
+        let transport = ScriptedTransport::new([
+            ReadStep::Pending,
+            ReadStep::Data(encode_command(1, method.clone(), b"response")),
+        ]);
+        let mut client = LocoClient::new(transport.clone())
+            .with_clock(Arc::new(clock.clone()))
+            .with_clock_skew_callback({
+                let skews = skews.clone();
+                Arc::new(move |skew| skews.lock().unwrap().push(skew))
+            });
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut request = pin!(client.request_timed(method.clone(), b""));
+
+        assert!(request.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(transport.written(), encode_command(1, method, b""));
+
+        // The clock goes back between writing the request and reading its response
+        clock.set(earlier);
+        let Poll::Ready(Ok((response, elapsed))) = request.as_mut().poll(&mut cx) else {
+            panic!("response was not read");
+        };
+
+        assert_eq!(&*response.data, b"response");
+        assert_eq!(elapsed, Duration::ZERO);
+
+        let skews = skews.lock().unwrap();
+        assert_eq!(skews.len(), 1);
+        assert_eq!(skews[0].behind(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Waker},
+};
+
+/// Signal ending reads of every client it is given to with [`crate::LocoClient::with_close_signal`].
+///
+/// Once closed, reads fail with [`crate::error::LocoError::Closed`] even in the middle of a
+/// command, and streams over the client end. Bytes buffered up to then are dropped and the
+/// read side stays ended.
+/// Clones share the same signal.
+#[derive(Debug, Clone, Default)]
+pub struct CloseSignal {
+    state: Arc<Mutex<CloseState>>,
+}
+
+#[derive(Debug, Default)]
+struct CloseState {
+    closed: bool,
+    wakers: Vec<Waker>,
+
+    /// Signals created by [`CloseSignal::child`], closed along with this one
+    children: Vec<Weak<Mutex<CloseState>>>,
+}
+
+impl CloseSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn close(&self) {
+        let (wakers, children) = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+
+            (mem::take(&mut state.wakers), mem::take(&mut state.children))
+        };
+
+        wakers.into_iter().for_each(Waker::wake);
+        for state in children.iter().filter_map(Weak::upgrade) {
+            CloseSignal { state }.close();
+        }
+    }
+
+    /// New signal which is also closed when this one is, while closing it leaves this one open
+    pub fn child(&self) -> Self {
+        let child = Self::new();
+
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            child.state.lock().unwrap().closed = true;
+        } else {
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.state));
+        }
+
+        child
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Check if closed, registering to be woken on close otherwise
+    pub(crate) fn poll_closed(&self, cx: &mut Context) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.closed && !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+
+        state.closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloseSignal;
+
+    #[test]
+    fn children_close_with_their_parent() {
+        let parent = CloseSignal::new();
+        let child = parent.child();
+        let closed_alone = parent.child();
+
+        closed_alone.close();
+        assert!(!parent.is_closed() && !child.is_closed());
+
+        parent.close();
+        assert!(child.is_closed());
+        assert!(parent.child().is_closed());
+    }
+}
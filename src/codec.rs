@@ -0,0 +1,284 @@
+use std::{error::Error, io};
+
+use futures_io::{AsyncRead, AsyncWrite};
+use loco_protocol::command::Method;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{error::LocoError, LocoClient};
+
+pub trait PayloadCodec {
+    type Error: Error + Send + Sync + 'static;
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, Self::Error>;
+}
+
+#[cfg(feature = "bson")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BsonCodec;
+
+#[cfg(feature = "bson")]
+impl PayloadCodec for BsonCodec {
+    type Error = BsonError;
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        Ok(bson::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, Self::Error> {
+        Ok(bson::from_slice(data)?)
+    }
+}
+
+#[cfg(feature = "bson")]
+#[derive(Debug)]
+pub enum BsonError {
+    Encode(bson::ser::Error),
+    Decode(bson::de::Error),
+}
+
+#[cfg(feature = "bson")]
+impl From<bson::ser::Error> for BsonError {
+    fn from(err: bson::ser::Error) -> Self {
+        Self::Encode(err)
+    }
+}
+
+#[cfg(feature = "bson")]
+impl From<bson::de::Error> for BsonError {
+    fn from(err: bson::de::Error) -> Self {
+        Self::Decode(err)
+    }
+}
+
+#[cfg(feature = "bson")]
+impl std::fmt::Display for BsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BsonError::Encode(err) => write!(f, "bson encode error: {err}"),
+            BsonError::Decode(err) => write!(f, "bson decode error: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "bson")]
+impl Error for BsonError {}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl PayloadCodec for JsonCodec {
+    type Error = serde_json::Error;
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(data)
+    }
+}
+
+/// [`LocoClient`] sending and receiving command bodies encoded with `C`,
+/// `BsonCodec` by default with the `bson` feature
+#[derive(Debug)]
+pub struct TypedClient<
+    T: Clone,
+    #[cfg(feature = "bson")] C = BsonCodec,
+    #[cfg(not(feature = "bson"))] C,
+> {
+    codec: C,
+    client: LocoClient<T>,
+}
+
+#[cfg(feature = "bson")]
+pub type BsonClient<T> = TypedClient<T, BsonCodec>;
+
+impl<T: Clone, C: PayloadCodec> TypedClient<T, C> {
+    pub const fn new(client: LocoClient<T>, codec: C) -> Self {
+        Self { codec, client }
+    }
+
+    pub const fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub const fn client(&self) -> &LocoClient<T> {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut LocoClient<T> {
+        &mut self.client
+    }
+
+    pub fn into_client(self) -> LocoClient<T> {
+        self.client
+    }
+
+    fn encode<R: Serialize>(&self, value: &R) -> io::Result<Vec<u8>> {
+        self.codec
+            .encode(value)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+}
+
+impl<T: AsyncWrite + Unpin + Clone, C: PayloadCodec> TypedClient<T, C> {
+    pub async fn send_typed<R: Serialize>(&mut self, method: Method, data: &R) -> io::Result<u32> {
+        let data = self.encode(data)?;
+
+        self.client.send(method, &data).await
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Clone, C: PayloadCodec> TypedClient<T, C> {
+    pub async fn request_typed<R: Serialize, D: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        data: &R,
+    ) -> io::Result<D> {
+        let data = self.encode(data)?;
+
+        let response = self.client.request(method, &data).await?.await?;
+
+        self.codec.decode(&response.data).map_err(|err| {
+            LocoError::Decode {
+                method: response.header.method,
+                id: response.header.id,
+                status: response.header.status,
+                data_size: response.data.len(),
+                source: Box::new(err),
+            }
+            .into()
+        })
+    }
+}
+
+/// Command with a bson body built field by field, for ad-hoc bodies without a struct
+///
+/// ```
+/// use futures_loco_protocol::codec::CommandBuilder;
+///
+/// let command = CommandBuilder::new("WRITE")
+///     .unwrap()
+///     .field("chatId", 123_i64)
+///     .field("msg", "hello");
+/// assert_eq!(&*command.method(), "WRITE");
+///
+/// assert!(CommandBuilder::new("WAYTOOLONGNAME").is_err());
+/// ```
+#[cfg(feature = "bson")]
+#[derive(Debug, Clone)]
+pub struct CommandBuilder {
+    method: Method,
+    document: bson::Document,
+}
+
+#[cfg(feature = "bson")]
+impl CommandBuilder {
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `method` is empty,
+    /// longer than 11 bytes or contains `\0`
+    pub fn new(method: &str) -> io::Result<Self> {
+        let method = Method::new(method)
+            .filter(|method| !method.is_empty() && !method.contains('\0'))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "method name must be 1 to 11 bytes without nul bytes",
+                )
+            })?;
+
+        Ok(Self {
+            method,
+            document: bson::Document::new(),
+        })
+    }
+
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<bson::Bson>) -> Self {
+        self.document.insert(key, value);
+        self
+    }
+
+    pub fn method(&self) -> Method {
+        self.method.clone()
+    }
+
+    pub const fn document(&self) -> &bson::Document {
+        &self.document
+    }
+
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        bson::to_vec(&self.document).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+
+    /// Write the command with a new id and flush, returning the id
+    pub async fn send<T: AsyncWrite + Unpin + Clone>(
+        &self,
+        client: &mut LocoClient<T>,
+    ) -> io::Result<u32> {
+        let data = self.encode()?;
+
+        client.send(self.method(), &data).await
+    }
+
+    /// Send the command as a request, see [`LocoClient::request`]
+    pub async fn request<T: AsyncRead + AsyncWrite + Unpin + Clone>(
+        &self,
+        client: &mut LocoClient<T>,
+    ) -> io::Result<loco_protocol::command::BoxedCommand> {
+        let data = self.encode()?;
+
+        client.request(self.method(), &data).await?.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "bson")]
+    #[test]
+    fn typed_client_defaults_to_bson() {
+        use super::{BsonCodec, TypedClient};
+        use crate::{test_util::ScriptedTransport, LocoClient};
+
+        let client: TypedClient<ScriptedTransport> =
+            TypedClient::new(LocoClient::new(ScriptedTransport::default()), BsonCodec);
+
+        let _: &BsonCodec = client.codec();
+    }
+
+    #[cfg(feature = "bson")]
+    #[test]
+    fn mismatched_body_is_decode_error() {
+        use loco_protocol::command::Method;
+        use serde::Deserialize;
+
+        use super::{BsonClient, BsonCodec};
+        use crate::{
+            error::LocoError,
+            frame::encode_command,
+            test_util::{block_on, ScriptedTransport},
+            LocoClient,
+        };
+
+        #[derive(Debug, Deserialize)]
+        struct Profile {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let method = Method::new("GETPF").unwrap();
+        let body = bson::to_vec(&bson::doc! { "name": 5 }).unwrap();
+        let transport = ScriptedTransport::from_chunks([encode_command(1, method.clone(), &body)]);
+
+        let mut client = BsonClient::new(LocoClient::new(transport), BsonCodec);
+        let err = block_on(client.request_typed::<_, Profile>(method, &bson::doc! {})).unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::Decode { id: 1, data_size, .. }) if *data_size == body.len()
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fmt::Debug, io};
+
+/// Inflates command bodies of the `data_type` it is registered for
+/// with [`LocoClient::with_decompressor`](crate::LocoClient::with_decompressor)
+pub trait Decompressor: Debug + Send + Sync {
+    fn decompress(&self, data: &[u8]) -> io::Result<Box<[u8]>>;
+}
+
+/// Deflates outbound command bodies chosen by a [`CompressionPolicy`]
+pub trait Compressor: Debug + Send + Sync {
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+}
+
+/// Which outbound bodies get compressed, and the `data_type` marking them.
+///
+/// Only applies to commands the client builds itself with
+/// [`LocoClient::write`](crate::LocoClient::write),
+/// [`LocoClient::write_priority`](crate::LocoClient::write_priority) and
+/// [`LocoClient::begin_command`](crate::LocoClient::begin_command).
+/// Received bodies are decompressed by their own `data_type` regardless of this policy.
+///
+/// ```
+/// # use std::{pin::Pin, sync::Arc};
+/// # use futures_loco_protocol::{compression::{CompressionPolicy, Compressor}, frame::decode_command, LocoClient};
+/// # use loco_protocol::command::Method;
+/// #[derive(Debug)]
+/// struct Reverse;
+///
+/// impl Compressor for Reverse {
+///     fn compress(&self, data: &[u8]) -> Vec<u8> {
+///         data.iter().rev().copied().collect()
+///     }
+/// }
+///
+/// let policy = CompressionPolicy { min_size: 4, data_type: 8 };
+/// let mut client = LocoClient::new(Vec::new()).with_compression(policy, Arc::new(Reverse));
+///
+/// Pin::new(&mut client).write(Method::new("MSG").unwrap(), b"abcd");
+/// Pin::new(&mut client).write(Method::new("MSG").unwrap(), b"abcde");
+///
+/// let written = client.writable_slices().0;
+/// let (small, used) = decode_command(written).unwrap().unwrap();
+/// let (large, _) = decode_command(&written[used..]).unwrap().unwrap();
+///
+/// assert_eq!((small.header.data_type, &*small.data), (0, &b"abcd"[..]));
+/// assert_eq!((large.header.data_type, &*large.data), (8, &b"edcba"[..]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// Bodies of at most this many bytes are sent uncompressed
+    pub min_size: usize,
+
+    /// `data_type` of compressed commands, as expected by the server
+    pub data_type: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Arc};
+
+    use loco_protocol::command::{Command, Header, Method};
+
+    use super::Decompressor;
+    use crate::{frame::encode_raw_command, test_util::ScriptedTransport, LocoClient};
+
+    #[derive(Debug)]
+    struct Reverse;
+
+    impl Decompressor for Reverse {
+        fn decompress(&self, data: &[u8]) -> io::Result<Box<[u8]>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+    }
+
+    fn command(id: u32, data_type: u8, data: &[u8]) -> Vec<u8> {
+        encode_raw_command(Command {
+            header: Header {
+                id,
+                status: 0,
+                method: Method::new("MSG").unwrap(),
+                data_type,
+            },
+            data,
+        })
+    }
+
+    #[test]
+    fn mixed_stream_inflates_only_compressed_commands() {
+        let inbound = [
+            command(1, 0, b"abc"),
+            command(2, 8, b"fed"),
+            command(3, 0, b"ihg"),
+            command(4, 8, b"lkj"),
+        ]
+        .concat();
+
+        let mut client = LocoClient::new(ScriptedTransport::from_chunks([inbound]))
+            .with_decompressor(8, Arc::new(Reverse));
+
+        let reads = client
+            .pump_reads()
+            .into_iter()
+            // Followed by the end of the stream
+            .take(4)
+            .map(|read| read.unwrap())
+            .map(|command| (command.header.data_type, command.data))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            reads,
+            [
+                (0, b"abc"[..].into()),
+                (8, b"def"[..].into()),
+                (0, b"ihg"[..].into()),
+                (8, b"jkl"[..].into()),
+            ]
+        );
+    }
+}
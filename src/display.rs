@@ -0,0 +1,74 @@
+use std::fmt::{self, Debug, Display};
+
+use loco_protocol::command::Command;
+
+/// Readable view of a [`Command`] for logging, previewing the first bytes of the body
+pub struct CommandDisplay<'a, T: ?Sized> {
+    command: &'a Command<T>,
+    preview: usize,
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> CommandDisplay<'a, T> {
+    pub const DEFAULT_PREVIEW: usize = 32;
+
+    pub const fn new(command: &'a Command<T>) -> Self {
+        Self::with_preview(command, Self::DEFAULT_PREVIEW)
+    }
+
+    pub const fn with_preview(command: &'a Command<T>, preview: usize) -> Self {
+        Self { command, preview }
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> Display for CommandDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = &self.command.header;
+        let data = self.command.data.as_ref();
+
+        write!(
+            f,
+            "#{} {} status={} data_type={} size={}",
+            header.id,
+            header.method.trim_end_matches('\0'),
+            header.status,
+            header.data_type,
+            data.len()
+        )?;
+
+        let preview = &data[..data.len().min(self.preview)];
+        if preview.is_empty() {
+            return Ok(());
+        }
+
+        f.write_str(" [")?;
+        for (i, byte) in preview.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+
+            write!(f, "{byte:02x}")?;
+        }
+
+        f.write_str("] |")?;
+        for &byte in preview {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                write!(f, "{}", byte as char)?;
+            } else {
+                f.write_str(".")?;
+            }
+        }
+        f.write_str("|")?;
+
+        if data.len() > preview.len() {
+            write!(f, " +{} bytes", data.len() - preview.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> Debug for CommandDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
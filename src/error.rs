@@ -0,0 +1,202 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io::{self, ErrorKind},
+};
+
+use loco_protocol::command::Method;
+
+use crate::status::Status;
+
+/// Protocol errors, returned wrapped in [`io::Error`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LocoError {
+    BufferLimitExceeded {
+        buffered: usize,
+        limit: usize,
+    },
+
+    /// Stream ended after a command header, before its whole body arrived
+    TruncatedPacket {
+        expected: u32,
+        received: usize,
+    },
+
+    /// Next header does not look valid, framing is probably misaligned
+    Desynchronized {
+        bytes: Vec<u8>,
+    },
+
+    /// Message of a message oriented stream ended inside a command header,
+    /// see [`crate::LocoClient::with_message_oriented`]
+    SplitHeader {
+        received: usize,
+    },
+
+    /// Method field of a received header is not UTF-8, or not a nul padded name
+    /// under [`crate::MethodValidation::Strict`]
+    InvalidMethod {
+        bytes: Vec<u8>,
+    },
+
+    /// Command body could not be decoded with a payload codec
+    Decode {
+        method: Method,
+        id: u32,
+        status: u16,
+        data_size: usize,
+        source: Box<dyn Error + Send + Sync>,
+    },
+
+    /// Command was answered with a status other than [`Status::SUCCESS`]
+    Status(Status),
+
+    /// Response arrived for a request which was answered already
+    DuplicateResponse {
+        id: u32,
+    },
+
+    /// Response method is not allowed for its request,
+    /// see [`crate::LocoClient::with_allowed_responses`]
+    UnexpectedResponseMethod {
+        request: Method,
+        response: Method,
+        id: u32,
+    },
+
+    /// Response of a pipelined request arrived before responses of earlier requests
+    OutOfOrder {
+        expected: u32,
+        received: u32,
+    },
+
+    /// Reads were ended by a [`crate::close::CloseSignal`]
+    Closed,
+
+    /// Internal state was left inconsistent by a panic during an earlier poll
+    Corrupted,
+}
+
+impl LocoError {
+    /// Get the [`LocoError`] wrapped in an [`io::Error`] returned by this crate
+    pub fn from_io(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    pub const fn kind(&self) -> ErrorKind {
+        match self {
+            LocoError::BufferLimitExceeded { .. } => ErrorKind::OutOfMemory,
+            LocoError::TruncatedPacket { .. } => ErrorKind::UnexpectedEof,
+            LocoError::Desynchronized { .. } => ErrorKind::InvalidData,
+            LocoError::SplitHeader { .. } => ErrorKind::InvalidData,
+            LocoError::InvalidMethod { .. } => ErrorKind::InvalidData,
+            LocoError::Decode { .. } => ErrorKind::InvalidData,
+            LocoError::Status(_) => ErrorKind::Other,
+            LocoError::DuplicateResponse { .. } => ErrorKind::InvalidData,
+            LocoError::UnexpectedResponseMethod { .. } => ErrorKind::InvalidData,
+            LocoError::OutOfOrder { .. } => ErrorKind::InvalidData,
+            LocoError::Closed => ErrorKind::ConnectionAborted,
+            LocoError::Corrupted => ErrorKind::Other,
+        }
+    }
+}
+
+impl Display for LocoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocoError::BufferLimitExceeded { buffered, limit } => write!(
+                f,
+                "buffer limit exceeded ({buffered} bytes buffered, limit {limit} bytes)"
+            ),
+
+            LocoError::TruncatedPacket { expected, received } => write!(
+                f,
+                "stream ended with truncated packet (expected {expected} body bytes, received {received})"
+            ),
+
+            LocoError::Desynchronized { bytes } => {
+                write!(f, "stream desynchronized at header {bytes:02x?}")
+            }
+
+            LocoError::SplitHeader { received } => write!(
+                f,
+                "message ended inside a command header ({received} of {} bytes)",
+                crate::HEADER_SIZE
+            ),
+
+            LocoError::InvalidMethod { bytes } => {
+                write!(f, "invalid method field {bytes:02x?}")
+            }
+
+            LocoError::Decode {
+                method,
+                id,
+                status,
+                data_size,
+                source,
+            } => write!(
+                f,
+                "cannot decode {}#{id} (status {status}, {data_size} bytes): {source}",
+                &**method
+            ),
+
+            LocoError::Status(status) => write!(f, "command failed with status {status}"),
+
+            LocoError::DuplicateResponse { id } => write!(f, "duplicate response for request {id}"),
+
+            LocoError::UnexpectedResponseMethod {
+                request,
+                response,
+                id,
+            } => write!(
+                f,
+                "unexpected response method {} for {}#{id}",
+                &**response, &**request
+            ),
+
+            LocoError::OutOfOrder { expected, received } => write!(
+                f,
+                "response for request {received} arrived before request {expected} was answered"
+            ),
+
+            LocoError::Closed => f.write_str("reads were ended by a close signal"),
+
+            LocoError::Corrupted => f.write_str("state corrupted by a panic during an earlier poll"),
+        }
+    }
+}
+
+impl Error for LocoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LocoError::Decode { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+impl From<LocoError> for io::Error {
+    fn from(err: LocoError) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+/// Bytes still buffered when [`crate::LocoClient::try_into_inner`] was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferedDataError {
+    pub read: usize,
+    pub write: usize,
+}
+
+impl Display for BufferedDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} read and {} write bytes are still buffered",
+            self.read, self.write
+        )
+    }
+}
+
+impl Error for BufferedDataError {}
@@ -0,0 +1,71 @@
+use std::io;
+
+use loco_protocol::command::{
+    client::{LocoSink, LocoStream},
+    BoxedCommand, Command, Header, Method,
+};
+
+use crate::{check_method_field, MethodValidation, HEADER_SIZE};
+
+/// Encode a request with `id` into the exact bytes [`crate::LocoClient::write`] would buffer
+pub fn encode_command(id: u32, method: Method, data: &[u8]) -> Vec<u8> {
+    encode_raw_command(Command {
+        header: Header {
+            id,
+            status: 0,
+            method,
+            data_type: 0,
+        },
+        data,
+    })
+}
+
+/// Encode `command` with its header as is, like [`crate::LocoClient::write_command`]
+pub fn encode_raw_command(command: Command<&[u8]>) -> Vec<u8> {
+    let mut sink = LocoSink::new();
+    sink.send(command);
+
+    sink.write_buffer.into()
+}
+
+/// Decode a command from the start of `buf`.
+///
+/// Returns the command and the number of bytes it took, or `None` if `buf` holds an incomplete command.
+/// Fails with [`crate::error::LocoError::InvalidMethod`] if the method field is not UTF-8.
+///
+/// ```
+/// use futures_loco_protocol::{
+///     frame::{decode_command, encode_command},
+///     loco_protocol::command::Method,
+/// };
+///
+/// let bytes = encode_command(7, Method::new("PING").unwrap(), b"body");
+/// assert_eq!(bytes.len(), 22 + 4);
+///
+/// let (command, size) = decode_command(&bytes).unwrap().unwrap();
+/// assert_eq!(size, bytes.len());
+/// assert_eq!(command.header.id, 7);
+/// assert_eq!(&*command.header.method, "PING");
+/// assert_eq!(&*command.data, b"body");
+///
+/// assert!(decode_command(&bytes[..size - 1]).unwrap().is_none());
+///
+/// let mut invalid = bytes.clone();
+/// invalid[6] = 0xff;
+/// assert!(decode_command(&invalid).is_err());
+/// ```
+pub fn decode_command(buf: &[u8]) -> io::Result<Option<(BoxedCommand, usize)>> {
+    if let Some(header) = buf.get(..HEADER_SIZE) {
+        check_method_field(header.try_into().unwrap(), MethodValidation::Lenient)?;
+    }
+
+    let mut stream = LocoStream::new();
+    stream.read_buffer.extend(buf);
+
+    let Some(command) = stream.read() else {
+        return Ok(None);
+    };
+    let size = buf.len() - stream.read_buffer.len();
+
+    Ok(Some((command, size)))
+}
@@ -0,0 +1,175 @@
+use std::time::{Duration, Instant};
+
+use crate::{clock::Clock, LocoClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+
+    /// Nothing was read or written for the idle threshold
+    Idle,
+
+    /// Buffered commands were not handed to the inner writer for the stall threshold
+    Stalled,
+}
+
+/// Snapshot from [`LocoClient::health`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Health {
+    pub status: HealthStatus,
+
+    pub pending_write_bytes: usize,
+    pub read_buffered: usize,
+    pub bytes_flushed_total: u64,
+
+    pub last_read: Option<Instant>,
+    pub last_write: Option<Instant>,
+}
+
+/// Thresholds used by [`LocoClient::health`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthThresholds {
+    pub idle: Duration,
+    pub stall: Duration,
+}
+
+impl HealthThresholds {
+    pub const DEFAULT: Self = Self {
+        idle: Duration::from_secs(60),
+        stall: Duration::from_secs(30),
+    };
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Activity timestamps taken with the client clock
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Activity {
+    last_read: Option<Instant>,
+    last_write: Option<Instant>,
+
+    /// When the write buffer last became non-empty
+    write_queued: Option<Instant>,
+}
+
+impl Activity {
+    pub(crate) const fn new() -> Self {
+        Self {
+            last_read: None,
+            last_write: None,
+            write_queued: None,
+        }
+    }
+
+    pub(crate) fn record_read(&mut self, clock: &dyn Clock) {
+        self.last_read = Some(clock.now());
+    }
+
+    pub(crate) fn record_write(&mut self, clock: &dyn Clock) {
+        self.last_write = Some(clock.now());
+    }
+
+    /// Take write activity from the write half of a split client
+    pub(crate) fn reunite_write(&mut self, write: Activity) {
+        self.last_write = self.last_write.max(write.last_write);
+        self.write_queued = write.write_queued;
+    }
+
+    pub(crate) fn record_queued(&mut self, clock: &dyn Clock, pending_before: usize) {
+        if pending_before == 0 {
+            self.write_queued = Some(clock.now());
+        }
+    }
+}
+
+impl<T: Clone> LocoClient<T> {
+    /// Liveness snapshot using the thresholds from [`Self::with_health_thresholds`].
+    ///
+    /// A client without any activity so far is [`HealthStatus::Idle`].
+    pub fn health(&self) -> Health {
+        let thresholds = self.config.health_thresholds;
+        let activity = &self.activity;
+        let now = self.clock().now();
+
+        let pending_write_bytes = self.pending_write_bytes();
+
+        let write_progress = activity.last_write.max(activity.write_queued);
+        let stalled = pending_write_bytes > 0
+            && write_progress
+                .is_some_and(|at| now.saturating_duration_since(at) >= thresholds.stall);
+
+        let idle = activity
+            .last_read
+            .max(activity.last_write)
+            .is_none_or(|at| now.saturating_duration_since(at) >= thresholds.idle);
+
+        Health {
+            status: if stalled {
+                HealthStatus::Stalled
+            } else if idle {
+                HealthStatus::Idle
+            } else {
+                HealthStatus::Healthy
+            },
+
+            pending_write_bytes,
+            read_buffered: self.stream.read_buffer.len(),
+            bytes_flushed_total: self.bytes_flushed_total,
+
+            last_read: activity.last_read,
+            last_write: activity.last_write,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::Pin, sync::Arc, time::Duration};
+
+    use loco_protocol::command::Method;
+
+    use super::{HealthStatus, HealthThresholds};
+    use crate::{
+        clock::ManualClock,
+        frame::encode_command,
+        test_util::{block_on, ScriptedTransport},
+        LocoClient,
+    };
+
+    #[test]
+    fn status_follows_activity() {
+        let clock = ManualClock::new();
+        let method = Method::new("MSG").unwrap();
+        let transport = ScriptedTransport::from_chunks([encode_command(1, method.clone(), b"")]);
+
+        let mut client = LocoClient::new(transport)
+            .with_clock(Arc::new(clock.clone()))
+            .with_health_thresholds(HealthThresholds {
+                idle: Duration::from_secs(60),
+                stall: Duration::from_secs(30),
+            });
+        assert_eq!(client.health().status, HealthStatus::Idle);
+
+        block_on(client.read()).unwrap();
+        assert_eq!(client.health().status, HealthStatus::Healthy);
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(client.health().status, HealthStatus::Healthy);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(client.health().status, HealthStatus::Idle);
+
+        // Buffered but never handed to the writer
+        Pin::new(&mut client).write(method, b"data");
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(client.health().status, HealthStatus::Idle);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(client.health().status, HealthStatus::Stalled);
+
+        assert!(client.pump_writes().is_ready());
+        assert_eq!(client.health().status, HealthStatus::Healthy);
+    }
+}
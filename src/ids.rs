@@ -0,0 +1,197 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+use loco_protocol::command::Method;
+
+/// Id ranges assigned to requests of method groups, for servers expecting
+/// ids partitioned by command type.
+///
+/// Requests of methods outside every namespace keep the sequential id, skipping the ranges.
+///
+/// ```
+/// use futures_loco_protocol::{ids::IdNamespaces, loco_protocol::command::Method};
+///
+/// let mut namespaces = IdNamespaces::new()
+///     .with_namespace([Method::new("MSG").unwrap()], 100..=199)
+///     .unwrap();
+/// assert!(namespaces
+///     .clone()
+///     .with_namespace([Method::new("PING").unwrap()], 150..=250)
+///     .is_err());
+///
+/// let mut current_id = 98;
+/// let ping = Method::new("PING").unwrap();
+/// let msg = Method::new("MSG").unwrap();
+///
+/// assert_eq!(namespaces.next_id(&mut current_id, &ping), 99);
+/// assert_eq!(namespaces.next_id(&mut current_id, &msg), 100);
+/// assert_eq!(namespaces.next_id(&mut current_id, &msg), 101);
+/// assert_eq!(namespaces.next_id(&mut current_id, &ping), 200);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IdNamespaces {
+    namespaces: Vec<Namespace>,
+}
+
+#[derive(Debug, Clone)]
+struct Namespace {
+    methods: Vec<Method>,
+    range: RangeInclusive<u32>,
+    next: u32,
+}
+
+impl IdNamespaces {
+    pub const fn new() -> Self {
+        Self {
+            namespaces: Vec::new(),
+        }
+    }
+
+    /// Assign ids of requests of `methods` from `range`, wrapping back to its start once exhausted.
+    /// Id `0` is reserved for pushes and cannot be part of a range, and at least one id must
+    /// be left for methods outside every namespace.
+    pub fn with_namespace(
+        mut self,
+        methods: impl IntoIterator<Item = Method>,
+        range: RangeInclusive<u32>,
+    ) -> Result<Self, IdRangeOverlap> {
+        // Ids `1..=u32::MAX` covered by namespaces with this one added
+        let covered = || {
+            self.namespaces
+                .iter()
+                .map(|namespace| &namespace.range)
+                .chain([&range])
+                .map(|range| u64::from(range.end() - range.start()) + 1)
+                .sum::<u64>()
+        };
+
+        let overlapping = range.is_empty()
+            || *range.start() == 0
+            || self.namespaces.iter().any(|namespace| {
+                range.start() <= namespace.range.end() && namespace.range.start() <= range.end()
+            })
+            || covered() >= u64::from(u32::MAX);
+
+        if overlapping {
+            return Err(IdRangeOverlap { range });
+        }
+
+        self.namespaces.push(Namespace {
+            methods: methods.into_iter().collect(),
+            next: *range.start(),
+            range,
+        });
+
+        Ok(self)
+    }
+
+    /// Check if `id` falls in any namespace range
+    pub fn contains(&self, id: u32) -> bool {
+        self.namespaces
+            .iter()
+            .any(|namespace| namespace.range.contains(&id))
+    }
+
+    /// Allocate the id of a request of `method`.
+    ///
+    /// Methods outside every namespace advance `current_id` past the ranges instead,
+    /// wrapping back to `1` after [`u32::MAX`].
+    pub fn next_id(&mut self, current_id: &mut u32, method: &Method) -> u32 {
+        if let Some(namespace) = self
+            .namespaces
+            .iter_mut()
+            .find(|namespace| namespace.methods.contains(method))
+        {
+            let id = namespace.next;
+            namespace.next = id
+                .checked_add(1)
+                .filter(|next| namespace.range.contains(next))
+                .unwrap_or(*namespace.range.start());
+
+            return id;
+        }
+
+        // Terminates as namespaces always leave an id outside of them
+        let mut id = current_id.checked_add(1).unwrap_or(1);
+        while let Some(namespace) = self
+            .namespaces
+            .iter()
+            .find(|namespace| namespace.range.contains(&id))
+        {
+            id = namespace.range.end().checked_add(1).unwrap_or(1);
+        }
+
+        *current_id = id;
+        id
+    }
+}
+
+/// Namespace range is empty, includes id `0`, overlaps a previous namespace or
+/// leaves no id outside of namespaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdRangeOverlap {
+    pub range: RangeInclusive<u32>,
+}
+
+impl Display for IdRangeOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "id range {}..={} is empty, includes 0, overlaps another namespace or leaves no free id",
+            self.range.start(),
+            self.range.end()
+        )
+    }
+}
+
+impl Error for IdRangeOverlap {}
+
+#[cfg(test)]
+mod tests {
+    use loco_protocol::command::Method;
+
+    use super::IdNamespaces;
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    #[test]
+    fn ids_wrap_at_u32_max() {
+        let mut namespaces = IdNamespaces::new()
+            .with_namespace([method("MSG")], u32::MAX - 1..=u32::MAX)
+            .unwrap()
+            .with_namespace([method("PING")], 1..=2)
+            .unwrap();
+
+        assert_eq!(namespaces.next_id(&mut 0, &method("MSG")), u32::MAX - 1);
+        assert_eq!(namespaces.next_id(&mut 0, &method("MSG")), u32::MAX);
+        assert_eq!(namespaces.next_id(&mut 0, &method("MSG")), u32::MAX - 1);
+
+        // Skips the range ending at `u32::MAX`, then wraps past the one at the start
+        let mut current_id = u32::MAX - 2;
+        assert_eq!(namespaces.next_id(&mut current_id, &method("GETMEM")), 3);
+        assert_eq!(current_id, 3);
+    }
+
+    #[test]
+    fn ranges_must_leave_a_free_id() {
+        assert!(IdNamespaces::new()
+            .with_namespace([method("MSG")], 1..=u32::MAX)
+            .is_err());
+
+        let namespaces = IdNamespaces::new()
+            .with_namespace([method("MSG")], 1..=100)
+            .unwrap();
+        assert!(namespaces
+            .clone()
+            .with_namespace([method("PING")], 101..=u32::MAX)
+            .is_err());
+        assert!(namespaces
+            .with_namespace([method("PING")], 102..=u32::MAX)
+            .is_ok());
+    }
+}
@@ -1,31 +1,109 @@
+#[cfg(feature = "actor")]
+pub mod actor;
+pub mod backpressure;
+pub mod clock;
+pub mod close;
+pub mod codec;
+pub mod compression;
+pub mod display;
+pub mod error;
+pub mod frame;
+pub mod health;
+pub mod ids;
+#[cfg(feature = "bson")]
+pub mod login;
+pub mod methods;
+pub mod pipeline;
+pub mod rate_limit;
+mod replay;
+pub mod router;
 pub mod secure;
 pub mod session;
+pub mod split;
+pub mod stats;
+pub mod status;
+pub mod writer;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 pub use loco_protocol;
 
+use backpressure::{Backpressure, BackpressureCallback};
+use clock::{Clock, ClockSkewCallback, SkewReporter, Sleep, SystemClock};
+use close::CloseSignal;
+use compression::{CompressionPolicy, Compressor, Decompressor};
+use error::{BufferedDataError, LocoError};
 use futures_core::Future;
 use futures_io::{AsyncRead, AsyncWrite};
+use health::{Activity, HealthThresholds};
+use ids::IdNamespaces;
 use loco_protocol::command::{
-    client::{LocoSink, LocoStream, StreamState},
+    client::{LocoSink, LocoStream, RawHeader, StreamState},
     BoxedCommand, Command, Header, Method,
 };
+use replay::ReplayBuffer;
+use serde::{Deserialize, Serialize};
+use stats::LocoStats;
+use status::Status;
 use std::{
+    any::Any,
+    borrow::Cow,
+    collections::VecDeque,
+    fmt::{self, Debug},
     future::poll_fn,
     io::{self, ErrorKind},
     mem,
+    ops::Range,
     pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
     task::{ready, Context, Poll},
+    time::{Duration, Instant},
 };
 
 pin_project_lite::pin_project!(
-    #[derive(Debug, Clone)]
+    /// Not `Clone`, as the buffered protocol state of [`LocoSink`] and [`LocoStream`]
+    /// cannot be cloned. Use [`LocoClient::split`] to read and write from different tasks.
+    #[derive(Debug)]
     pub struct LocoClient<T: Clone> {
         current_id: u32,
+        bytes_flushed_total: u64,
 
         sink: LocoSink,
+        frames: VecDeque<usize>,
+        frame_written: usize,
+        priority_sink: LocoSink,
+        // Consecutive inner writes which accepted zero bytes
+        zero_writes: usize,
+
         stream: LocoStream,
+        // Header bytes as received, once `stream` parsed them ahead of the body
+        parsed_header: Option<[u8; HEADER_SIZE]>,
 
         read_state: ReadState,
+        borrowed_len: usize,
+        borrowed_command: Option<BoxedCommand>,
+
+        config: Config,
+        flush_window: FlushWindow,
+        read_idle: ReadIdle,
+        stats: LocoStats,
+        activity: Activity,
+        shared_buffer: Option<SharedBuffer>,
+        replay: Option<ReplayBuffer>,
+
+        // Commands returned before reading more, queued by `inject_command`
+        // or kept by `request_raw`
+        injected: VecDeque<BoxedCommand>,
+
+        // Command whose body is being read by `poll_read_into`
+        read_into: Option<ReadInto>,
 
         #[pin]
         inner: T,
@@ -35,19 +113,322 @@ pin_project_lite::pin_project!(
 impl<T: Clone> LocoClient<T> {
     pub const MAX_READ_SIZE: u64 = 16 * 1024 * 1024;
 
+    /// Most body bytes handed to a [`Self::with_on_oversize`] callback
+    pub const MAX_OVERSIZE_AUDIT_PREFIX: usize = 4096;
+
+    /// How many consecutive [`ErrorKind::Interrupted`] errors from the inner transport
+    /// are retried before the error is returned
+    pub const MAX_INTERRUPTED_RETRIES: usize = 16;
+
     pub const fn new(inner: T) -> Self {
         Self {
             current_id: 0,
+            bytes_flushed_total: 0,
 
             sink: LocoSink::new(),
+            frames: VecDeque::new(),
+            frame_written: 0,
+            priority_sink: LocoSink::new(),
+            zero_writes: 0,
+
             stream: LocoStream::new(),
+            parsed_header: None,
 
             read_state: ReadState::Pending,
+            borrowed_len: 0,
+            borrowed_command: None,
+
+            config: Config::new(),
+            flush_window: FlushWindow {
+                deadline: None,
+                sleep: None,
+            },
+            read_idle: ReadIdle {
+                deadline: None,
+                sleep: None,
+            },
+            stats: LocoStats::new(),
+            activity: Activity::new(),
+            shared_buffer: None,
+            replay: None,
+
+            injected: VecDeque::new(),
+            read_into: None,
 
             inner,
         }
     }
 
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.config.clock = Some(clock);
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.config.clock.as_deref().unwrap_or(&SystemClock)
+    }
+
+    /// Answer server `PING` commands automatically, echoing their id and method with an empty body.
+    /// Method names can be changed with [`Self::with_heartbeat_methods`].
+    ///
+    /// Answered pings are not returned from reads. Replies are queued as priority commands
+    /// (see [`Self::write_priority`]) and go out with the next flush.
+    pub fn with_auto_pong(mut self, auto_pong: bool) -> Self {
+        self.config.auto_pong = auto_pong;
+        self
+    }
+
+    /// Method names of server pings answered by [`Self::with_auto_pong`] and of the replies.
+    /// By default `PING` is answered with `PING`.
+    pub fn with_heartbeat_methods(mut self, ping: Method, pong: Method) -> Self {
+        self.config.heartbeat_methods = Some((ping, pong));
+        self
+    }
+
+    /// Coalesce written commands for up to `window`, or until `max_bytes` are buffered,
+    /// before [`Self::send`] flushes them as one write.
+    ///
+    /// [`Self::poll_flush_window`] flushes once the window elapsed. Explicit flushes
+    /// ([`Self::poll_flush`], [`Self::send_flushed`], [`Self::drain`], [`Self::close`]) flush immediately.
+    pub fn with_flush_window(mut self, window: Duration, max_bytes: usize) -> Self {
+        self.config.flush_window = Some((window, max_bytes));
+        self
+    }
+
+    fn flush_window_due(&self) -> bool {
+        match (self.config.flush_window, self.flush_window.deadline) {
+            (Some((_, max_bytes)), Some(deadline)) => {
+                self.pending_write_bytes() >= max_bytes || self.clock().now() >= deadline
+            }
+
+            _ => true,
+        }
+    }
+
+    /// Limit combined read and write buffer size. Reading from the inner transport stops
+    /// with [`LocoError::BufferLimitExceeded`] while the limit would be exceeded,
+    /// and resumes once buffered commands are read or flushed.
+    ///
+    /// Halves from [`Self::split`] share the limit, counting bytes buffered by both.
+    pub fn with_max_total_buffer(mut self, bytes: usize) -> Self {
+        self.config.max_total_buffer = Some(bytes);
+        self
+    }
+
+    /// Decompress bodies of commands with `data_type` before they are returned.
+    /// Commands with other data types are returned as they arrived.
+    ///
+    /// The header is kept as received. Borrowed and raw reads return bodies as they arrived.
+    pub fn with_decompressor(mut self, data_type: u8, decompressor: Arc<dyn Decompressor>) -> Self {
+        self.config.decompressors.push((data_type, decompressor));
+        self
+    }
+
+    /// Compress outbound bodies larger than [`CompressionPolicy::min_size`] with `compressor`
+    pub fn with_compression(
+        mut self,
+        policy: CompressionPolicy,
+        compressor: Arc<dyn Compressor>,
+    ) -> Self {
+        self.config.compression = Some((policy, compressor));
+        self
+    }
+
+    /// Thresholds deciding [`health::HealthStatus`] in [`Self::health`]
+    pub fn with_health_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.config.health_thresholds = thresholds;
+        self
+    }
+
+    /// Never submit more than `bytes` to a single inner `poll_write`,
+    /// regardless of how the write buffer is laid out
+    pub fn with_max_write_chunk(mut self, bytes: usize) -> Self {
+        self.config.max_write_chunk = Some(bytes.max(1));
+        self
+    }
+
+    /// Retry an inner `poll_write` which accepted zero bytes up to `retries` consecutive times
+    /// before failing with [`ErrorKind::WriteZero`]. By default it fails immediately.
+    ///
+    /// Each retry yields to the executor first, waking the task to be polled again.
+    pub fn with_write_zero_policy(mut self, retries: usize) -> Self {
+        self.config.write_zero_retries = retries;
+        self
+    }
+
+    /// Assign ids of written commands from the id range of their method namespace.
+    /// [`Self::current_id`] only tracks ids of methods outside every namespace.
+    pub fn with_id_namespaces(mut self, namespaces: IdNamespaces) -> Self {
+        self.config.id_namespaces = Some(namespaces);
+        self
+    }
+
+    /// Call `callback` once buffered write bytes reach `high`, and again once they drop
+    /// to `low` afterwards. Bytes are counted like [`Self::pending_write_bytes`].
+    pub fn with_backpressure(
+        mut self,
+        high: usize,
+        low: usize,
+        callback: BackpressureCallback,
+    ) -> Self {
+        self.config.backpressure = Some(Backpressure::new(high, low, callback));
+        self
+    }
+
+    /// Fail reads with [`ErrorKind::TimedOut`] once nothing at all was read for `timeout`
+    /// while waiting on the inner reader. Any bytes read restart the timeout, so
+    /// slow but steady transfers are not cut off. Timed with [`Self::clock`].
+    pub fn with_read_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.read_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Report measurements where [`Self::clock`] went backwards, see [`clock::ClockSkew`].
+    /// Such measurements are clamped to zero whether or not a callback is set.
+    pub fn with_clock_skew_callback(mut self, callback: ClockSkewCallback) -> Self {
+        self.config.clock_skew = Some(SkewReporter(callback));
+        self
+    }
+
+    /// End reads once `signal` is closed, see [`CloseSignal`]
+    pub fn with_close_signal(mut self, signal: CloseSignal) -> Self {
+        self.config.close_signal = Some(signal);
+        self
+    }
+
+    /// Attach application data to this connection, see [`Self::set_context`]
+    pub fn with_context(mut self, context: impl Any + Send + Sync) -> Self {
+        self.set_context(context);
+        self
+    }
+
+    /// Attach application data to this connection, replacing any previous context.
+    /// Split halves share the context set before splitting.
+    pub fn set_context(&mut self, context: impl Any + Send + Sync) {
+        self.config.context = Some(Arc::new(context));
+    }
+
+    /// Context set with [`Self::set_context`], if it is a `C`
+    pub fn context<C: Any>(&self) -> Option<&C> {
+        self.config.context.as_deref()?.downcast_ref()
+    }
+
+    /// Check method fields of received headers as strictly as `validation`, see
+    /// [`MethodValidation`]. Method fields which are not UTF-8 are always rejected.
+    pub fn with_method_validation(mut self, validation: MethodValidation) -> Self {
+        self.config.method_validation = validation;
+        self
+    }
+
+    /// Treat the inner stream as message oriented, with each message carrying whole
+    /// commands like a WebSocket transport.
+    ///
+    /// A read ending inside a command header then fails with [`LocoError::SplitHeader`]
+    /// instead of being accumulated with following reads.
+    pub fn with_message_oriented(mut self, enabled: bool) -> Self {
+        self.config.message_oriented = enabled;
+        self
+    }
+
+    /// Fail [`Self::request`] and [`Self::request_raw`] of `request` with
+    /// [`LocoError::UnexpectedResponseMethod`] unless the response has one of `responses`.
+    /// Responses of requests without allowed methods registered are never checked.
+    pub fn with_allowed_responses(
+        mut self,
+        request: Method,
+        responses: impl IntoIterator<Item = Method>,
+    ) -> Self {
+        self.config
+            .allowed_responses
+            .push((request, responses.into_iter().collect()));
+        self
+    }
+
+    /// Call `audit` with the header and at most [`Self::MAX_OVERSIZE_AUDIT_PREFIX`] leading body
+    /// bytes of each command discarded by [`Self::reject_current_oversize`], once discarded
+    pub fn with_on_oversize(mut self, audit: impl FnMut(&Header, &[u8]) + Send + 'static) -> Self {
+        self.config.oversize_audit = Some(OversizeAudit(Arc::new(Mutex::new(audit))));
+        self
+    }
+
+    /// Handle received headers which do not look valid as `resync` says, see [`ResyncMode`]
+    pub fn with_resync(mut self, resync: ResyncMode) -> Self {
+        self.config.resync = resync;
+        self
+    }
+
+    /// Guess what a received command is from its id. Id `0` is a push, ids this client
+    /// already assigned are responses, and any other id is a server request expecting
+    /// a reply with the same id (see [`LocoClient::reply_to`]).
+    pub fn classify(&self, header: &Header) -> CommandKind {
+        match header.id {
+            0 => CommandKind::Push,
+            id if id <= self.current_id => CommandKind::Response,
+            id if self
+                .config
+                .id_namespaces
+                .as_ref()
+                .is_some_and(|namespaces| namespaces.contains(id)) =>
+            {
+                CommandKind::Response
+            }
+            _ => CommandKind::ServerRequest,
+        }
+    }
+
+    /// Id of the last written command
+    pub const fn current_id(&self) -> u32 {
+        self.current_id
+    }
+
+    /// Set the id baseline, so the next written command gets `id + 1`
+    pub fn set_current_id(&mut self, id: u32) {
+        self.current_id = id;
+    }
+
+    #[cfg(feature = "stats")]
+    pub const fn stats(&self) -> &LocoStats {
+        &self.stats
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats_mut(&mut self) -> &mut LocoStats {
+        &mut self.stats
+    }
+
+    pub fn pending_write_bytes(&self) -> usize {
+        self.sink.write_buffer.len() + self.priority_sink.write_buffer.len()
+    }
+
+    pub const fn bytes_flushed_total(&self) -> u64 {
+        self.bytes_flushed_total
+    }
+
+    /// Returns `true` if a complete command within [`Self::MAX_READ_SIZE`] is already buffered,
+    /// so the next read resolves without touching the inner reader.
+    pub fn read_ready(&self) -> bool {
+        matches!(self.read_state, ReadState::Pending)
+            && command_ready(&self.stream, self.borrowed_len, Self::MAX_READ_SIZE)
+    }
+
+    /// Methods of every complete command in the read buffer, in arrival order, without consuming them
+    pub fn peek_buffered_methods(&self) -> Vec<Method> {
+        let mut methods = Vec::new();
+
+        let mut offset = self.borrowed_len;
+        while let Some((header, header_size, data_size)) = buffered_header(&self.stream, offset) {
+            let end = offset + header_size + data_size as usize;
+            if self.stream.read_buffer.len() < end {
+                break;
+            }
+
+            methods.push(header.method);
+            offset = end;
+        }
+
+        methods
+    }
+
     pub const fn inner(&self) -> &T {
         &self.inner
     }
@@ -63,6 +444,198 @@ impl<T: Clone> LocoClient<T> {
     pub fn into_inner(self) -> T {
         self.inner
     }
+
+    /// Unwrap the inner transport only if no read or write bytes are buffered,
+    /// handing the client back along with how many bytes would be lost otherwise
+    pub fn try_into_inner(mut self) -> Result<T, (Box<Self>, BufferedDataError)> {
+        release_borrowed(
+            &mut self.stream,
+            &mut self.borrowed_len,
+            &mut self.borrowed_command,
+        );
+
+        let read = match self.stream.state() {
+            StreamState::Header(_) => HEADER_SIZE,
+            _ => 0,
+        } + self.stream.read_buffer.len();
+        let write = self.pending_write_bytes();
+
+        if read > 0 || write > 0 {
+            return Err((Box::new(self), BufferedDataError { read, write }));
+        }
+
+        Ok(self.inner)
+    }
+
+    /// Unwrap the inner transport along with every byte read from it but not consumed
+    /// as a command yet, including the header of a partially read command.
+    pub fn into_inner_with_buffered(mut self) -> (T, Vec<u8>) {
+        release_borrowed(
+            &mut self.stream,
+            &mut self.borrowed_len,
+            &mut self.borrowed_command,
+        );
+
+        let mut buffered = Vec::with_capacity(HEADER_SIZE + self.stream.read_buffer.len());
+
+        if let StreamState::Header(header) = self.stream.state() {
+            buffered.extend(
+                self.parsed_header
+                    .unwrap_or_else(|| encode_raw_header(header)),
+            );
+        }
+        buffered.extend(self.stream.read_buffer);
+
+        (self.inner, buffered)
+    }
+
+    /// Bytes to hand to the inner writer next, for writing them with external I/O.
+    /// Once written, report them with [`Self::advance_written`].
+    ///
+    /// Only a prefix of the write buffer is returned while a priority command is queued,
+    /// so call again after advancing until both slices are empty.
+    pub fn writable_slices(&self) -> (&[u8], &[u8]) {
+        if front_is_priority(self.frame_written, &self.priority_sink) {
+            self.priority_sink.write_buffer.as_slices()
+        } else {
+            self.sink.write_buffer.as_slices()
+        }
+    }
+
+    /// Remove `n` bytes written with external I/O from the front of [`Self::writable_slices`]
+    ///
+    /// # Panics
+    /// Panics if `n` is larger than the total length of [`Self::writable_slices`].
+    pub fn advance_written(&mut self, n: usize) {
+        advance_written(
+            &mut self.sink,
+            &mut self.frames,
+            &mut self.frame_written,
+            &mut self.priority_sink,
+            &mut self.bytes_flushed_total,
+            n,
+        );
+        self.activity.record_write(self.config.clock());
+        update_backpressure(&mut self.config, &self.sink, &self.priority_sink);
+
+        publish_buffered(
+            &mut self.shared_buffer,
+            &self.stream,
+            &self.sink,
+            &self.priority_sink,
+        );
+    }
+
+    /// Reserve `len` bytes at the end of the read buffer to be filled with external I/O.
+    ///
+    /// Reserved bytes count as read only after [`ReadReservation::fill_read`], dropping the
+    /// reservation without it discards them. The reservation borrows the client, so commands
+    /// cannot be parsed from bytes which are not filled yet.
+    pub fn reserve_read(&mut self, len: usize) -> ReadReservation<'_> {
+        let start = self.stream.read_buffer.len();
+        self.stream.read_buffer.resize(start + len, 0);
+
+        ReadReservation {
+            read_buffer: &mut self.stream.read_buffer,
+            start,
+            filled: 0,
+        }
+    }
+
+    /// Capture ids, buffered bytes and read state, to be restored over the same
+    /// connection with [`Self::thaw`]. Configuration is not captured.
+    pub fn freeze(&self) -> io::Result<Vec<u8>> {
+        let mut read_buffer = Vec::new();
+        if let StreamState::Header(header) = self.stream.state() {
+            read_buffer.extend(
+                self.parsed_header
+                    .unwrap_or_else(|| encode_raw_header(header)),
+            );
+        }
+        read_buffer.extend(self.stream.read_buffer.range(self.borrowed_len..));
+
+        bincode::serialize(&FrozenState {
+            version: FrozenState::VERSION,
+            current_id: self.current_id,
+            bytes_flushed_total: self.bytes_flushed_total,
+
+            write_buffer: self.sink.write_buffer.iter().copied().collect(),
+            frames: self.frames.iter().map(|&len| len as u64).collect(),
+            frame_written: self.frame_written as u64,
+            priority_buffer: self.priority_sink.write_buffer.iter().copied().collect(),
+
+            read_buffer,
+            read_state: match self.read_state {
+                ReadState::PacketTooLarge => FrozenReadState::PacketTooLarge,
+                ReadState::Done | ReadState::Closed => FrozenReadState::Done,
+                _ => FrozenReadState::Pending,
+            },
+        })
+        .map_err(io::Error::other)
+    }
+
+    /// Restore a client captured with [`Self::freeze`] over `inner`
+    pub fn thaw(inner: T, blob: &[u8]) -> io::Result<Self> {
+        let state = bincode::deserialize::<FrozenState>(blob)
+            .ok()
+            .filter(|state| state.version == FrozenState::VERSION)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "invalid frozen client state"))?;
+
+        let mut client = Self::new(inner);
+        client.current_id = state.current_id;
+        client.bytes_flushed_total = state.bytes_flushed_total;
+
+        client.sink.write_buffer = state.write_buffer.into();
+        client.frames = state.frames.into_iter().map(|len| len as usize).collect();
+        client.frame_written = state.frame_written as usize;
+        client.priority_sink.write_buffer = state.priority_buffer.into();
+
+        client.stream.read_buffer = state.read_buffer.into();
+        client.read_state = match state.read_state {
+            FrozenReadState::Pending => ReadState::Pending,
+            FrozenReadState::PacketTooLarge => ReadState::PacketTooLarge,
+            FrozenReadState::Done => ReadState::Done,
+        };
+
+        Ok(client)
+    }
+
+    /// Replace the inner transport while keeping ids, buffered commands and parse state.
+    ///
+    /// Bytes the old transport buffered internally (not yet handed over to this client,
+    /// or not yet flushed) are lost unless `f` carries them over.
+    pub fn map_inner<U: Clone>(self, f: impl FnOnce(T) -> U) -> LocoClient<U> {
+        LocoClient {
+            current_id: self.current_id,
+            bytes_flushed_total: self.bytes_flushed_total,
+
+            sink: self.sink,
+            frames: self.frames,
+            frame_written: self.frame_written,
+            priority_sink: self.priority_sink,
+            zero_writes: self.zero_writes,
+
+            stream: self.stream,
+            parsed_header: self.parsed_header,
+
+            read_state: self.read_state,
+            borrowed_len: self.borrowed_len,
+            borrowed_command: self.borrowed_command,
+
+            config: self.config,
+            flush_window: self.flush_window,
+            read_idle: self.read_idle,
+            stats: self.stats,
+            activity: self.activity,
+            shared_buffer: self.shared_buffer,
+            replay: self.replay,
+
+            injected: self.injected,
+            read_into: self.read_into,
+
+            inner: f(self.inner),
+        }
+    }
 }
 
 impl<T: AsyncRead + Clone> LocoClient<T> {
@@ -75,144 +648,2688 @@ impl<T: AsyncRead + Clone> LocoClient<T> {
         poll_fn(|cx| this.as_mut().poll_read(cx)).await
     }
 
-    pub fn poll_read(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<BoxedCommand>> {
-        let mut this = self.project();
+    /// Read a command appending its body to `buf`, see [`Self::poll_read_into`]
+    pub async fn read_into(&mut self, buf: &mut Vec<u8>) -> io::Result<(Header, Range<usize>)>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
 
-        let mut buffer = [0_u8; 1024];
-        loop {
-            match mem::replace(this.read_state, ReadState::Corrupted) {
-                ReadState::Pending => match this.stream.read() {
-                    Some(command) => {
-                        *this.read_state = ReadState::Pending;
-                        break Poll::Ready(Ok(command));
-                    }
+        poll_fn(|cx| this.as_mut().poll_read_into(cx, buf)).await
+    }
 
-                    None => {
-                        if let StreamState::Header(header) = this.stream.state() {
-                            if header.data_size as u64 > Self::MAX_READ_SIZE {
-                                *this.read_state = ReadState::PacketTooLarge;
-                                continue;
-                            }
-                        }
+    /// Read a command along with its [`CommandKind`], see [`LocoClient::classify`]
+    pub async fn read_tagged(&mut self) -> io::Result<(CommandKind, BoxedCommand)>
+    where
+        T: Unpin,
+    {
+        let command = self.read().await?;
 
-                        *this.read_state = ReadState::Pending;
+        Ok((self.classify(&command.header), command))
+    }
 
-                        let read = ready!(this.inner.as_mut().poll_read(cx, &mut buffer))?;
-                        if read == 0 {
-                            *this.read_state = ReadState::Done;
-                            continue;
-                        }
+    pub async fn read_batch(&mut self, max: usize) -> io::Result<Vec<BoxedCommand>>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
 
-                        this.stream.read_buffer.extend(&buffer[..read]);
-                    }
-                },
+        poll_fn(|cx| this.as_mut().poll_read_batch(cx, max)).await
+    }
 
-                ReadState::PacketTooLarge => {
-                    *this.read_state = ReadState::PacketTooLarge;
+    /// Wait for at least one command, then take up to `max` commands in total from bytes
+    /// that are already buffered. Stops early once [`Self::read_ready`] turns `false`,
+    /// so a batch never waits on the inner reader after its first command.
+    ///
+    /// A `max` of `0` returns an empty batch right away without reading.
+    pub fn poll_read_batch(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        max: usize,
+    ) -> Poll<io::Result<Vec<BoxedCommand>>> {
+        if max == 0 {
+            return Poll::Ready(Ok(Vec::new()));
+        }
 
-                    break Poll::Ready(Err(io::Error::new(
-                        ErrorKind::InvalidData,
-                        "packet is too large",
-                    )));
-                }
+        let first = ready!(self.as_mut().poll_read(cx))?;
 
-                ReadState::Done => break Poll::Ready(Err(ErrorKind::UnexpectedEof.into())),
+        let mut batch = vec![first];
+        while batch.len() < max && self.read_ready() {
+            let this = self.as_mut().project();
 
-                ReadState::Corrupted => unreachable!(),
+            match read_buffered(
+                this.stream,
+                this.parsed_header,
+                this.priority_sink,
+                this.config,
+                Self::MAX_READ_SIZE,
+            ) {
+                Ok(Some(command)) => {
+                    record_received(this.stats, this.replay, &command.header);
+                    batch.push(command);
+                }
+                _ => break,
             }
         }
+
+        Poll::Ready(Ok(batch))
     }
-}
 
-impl<T: AsyncWrite + Clone> LocoClient<T> {
-    pub async fn send(&mut self, method: Method, data: &[u8]) -> io::Result<u32>
+    /// Read a command borrowing its body from the read buffer, avoiding an allocation.
+    ///
+    /// The borrowed bytes are released on the next read of any kind, so the returned
+    /// command cannot outlive it. If a previous read already consumed the header of this
+    /// command, the body is allocated once as with [`Self::read`].
+    ///
+    /// ```compile_fail
+    /// # async fn read<T: futures_io::AsyncRead + Unpin + Clone>(
+    /// #     client: &mut futures_loco_protocol::LocoClient<T>,
+    /// # ) -> std::io::Result<()> {
+    /// let first = client.read_borrowed().await?;
+    /// let second = client.read_borrowed().await?; // first is still borrowed
+    /// println!("{:?} {:?}", first.header, second.header);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_borrowed(&mut self) -> io::Result<Command<&[u8]>>
     where
         T: Unpin,
     {
-        let mut this = Pin::new(self);
+        let mut this = Pin::new(&mut *self);
 
-        let id = this.as_mut().write(method, data);
+        poll_fn(|cx| this.as_mut().poll_read_ready(cx)).await?;
 
-        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+        if let StreamState::Header(_) = self.stream.state() {
+            let command = self.stream.read().ok_or(LocoError::Corrupted)?;
+            record_received(&mut self.stats, &mut self.replay, &command.header);
+            let command = self.borrowed_command.insert(command);
 
-        Ok(id)
+            return Ok(Command {
+                header: command.header.clone(),
+                data: &command.data,
+            });
+        }
+
+        let header = peek_raw_header(&self.stream.read_buffer, 0).ok_or(LocoError::Corrupted)?;
+        let size = HEADER_SIZE + header.data_size as usize;
+        self.borrowed_len = size;
+        record_received(&mut self.stats, &mut self.replay, &header.header);
+
+        Ok(Command {
+            header: header.header,
+            data: &self.stream.read_buffer.make_contiguous()[HEADER_SIZE..size],
+        })
     }
 
-    pub fn write(self: Pin<&mut Self>, method: Method, data: &[u8]) -> u32 {
-        let this = self.project();
+    /// Read a command keeping the exact bytes it arrived as, header included.
+    /// A header already parsed by an earlier unfinished read is kept as received too.
+    pub async fn read_raw(&mut self) -> io::Result<RawCommand>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(&mut *self);
 
-        let id = {
-            *this.current_id += 1;
+        poll_fn(|cx| this.as_mut().poll_read_ready(cx)).await?;
 
-            *this.current_id
-        };
+        let (bytes, header) = match self.stream.state() {
+            // Header bytes were consumed by the stream, so they come from the copy taken
+            // before parsing them
+            StreamState::Header(_) => {
+                let raw_header = self.parsed_header.ok_or(LocoError::Corrupted)?;
+                let command = self.stream.read().ok_or(LocoError::Corrupted)?;
 
-        this.sink.send(Command {
-            header: Header {
-                id,
-                status: 0,
-                method,
-                data_type: 0,
-            },
-            data,
-        });
+                let mut bytes = Vec::with_capacity(HEADER_SIZE + command.data.len());
+                bytes.extend(raw_header);
+                bytes.extend(command.data.iter());
 
-        id
-    }
+                (bytes.into_boxed_slice(), command.header)
+            }
 
-    pub fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        let mut this = self.project();
+            _ => {
+                let size = HEADER_SIZE
+                    + peek_raw_header(&self.stream.read_buffer, 0)
+                        .ok_or(LocoError::Corrupted)?
+                        .data_size as usize;
+                let bytes = self.stream.read_buffer.drain(..size).collect::<Box<[u8]>>();
 
-        while !this.sink.write_buffer.is_empty() {
-            let written = ready!(this.inner.as_mut().poll_write(cx, {
-                let slices = this.sink.write_buffer.as_slices();
+                let header = bincode::deserialize::<RawHeader>(&bytes[..HEADER_SIZE])
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?
+                    .header;
 
-                if !slices.0.is_empty() {
-                    slices.0
-                } else {
-                    slices.1
-                }
-            }))?;
+                (bytes, header)
+            }
+        };
+
+        record_received(&mut self.stats, &mut self.replay, &header);
+
+        Ok(RawCommand { header, bytes })
+    }
 
-            this.sink.write_buffer.drain(..written);
+    /// After a read failed because a command exceeded [`Self::MAX_READ_SIZE`], discard that
+    /// command so reading can continue. Returns the number of body bytes discarded,
+    /// or `0` without reading anything if no oversized command is pending.
+    ///
+    /// The connection cannot be used anymore if this is cancelled or fails midway.
+    pub async fn reject_current_oversize(&mut self) -> io::Result<u64>
+    where
+        T: Unpin,
+    {
+        if !matches!(self.read_state, ReadState::PacketTooLarge) {
+            return Ok(0);
         }
 
-        ready!(this.inner.poll_flush(cx))?;
+        let Some(OversizeAudit(audit)) = self.config.oversize_audit.clone() else {
+            return self.discard_front(None).await;
+        };
+        let Some((header, _, _)) = buffered_header(&self.stream, 0) else {
+            return Ok(0);
+        };
 
-        Poll::Ready(Ok(()))
+        let mut prefix = Vec::new();
+        let discarded = self.discard_front(Some(&mut prefix)).await?;
+        // A panicking audit leaves no state of the client half updated, so keep calling it
+        (audit.lock().unwrap_or_else(PoisonError::into_inner))(&header, &prefix);
+
+        Ok(discarded)
     }
-}
 
-impl<T: AsyncRead + AsyncWrite + Unpin + Clone> LocoClient<T> {
-    pub async fn request(
+    /// Read until a command with a header satisfying `pred` arrives, returning it.
+    ///
+    /// Bodies of other commands are discarded as they arrive instead of being buffered,
+    /// so they may exceed [`Self::MAX_READ_SIZE`]. The connection cannot be used anymore
+    /// if this is cancelled while discarding a body.
+    pub async fn read_filtered(
         &mut self,
-        method: Method,
-        data: &[u8],
-    ) -> io::Result<impl Future<Output = io::Result<BoxedCommand>> + '_> {
-        let mut this = Pin::new(self);
+        pred: impl Fn(&Header) -> bool,
+    ) -> io::Result<BoxedCommand>
+    where
+        T: Unpin,
+    {
+        loop {
+            let header = {
+                let mut this = Pin::new(&mut *self);
 
-        let id = this.as_mut().write(method, data);
+                poll_fn(|cx| this.as_mut().poll_header(cx)).await?
+            };
 
-        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+            if pred(&header) {
+                break self.read().await;
+            }
 
-        let read_task = async move {
-            Ok(loop {
-                let read = poll_fn(|cx| this.as_mut().poll_read(cx)).await?;
+            self.discard_front(None).await?;
+        }
+    }
+
+    /// Read until a command of `method` arrives, discarding other commands like
+    /// [`Self::read_filtered`] so their bodies are never buffered whole
+    pub async fn read_until(&mut self, method: &str) -> io::Result<BoxedCommand>
+    where
+        T: Unpin,
+    {
+        self.read_filtered(|header| &*header.method == method).await
+    }
+
+    /// Wait until the header of the next command is buffered
+    fn poll_header(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<Header>> {
+        let mut this = self.project();
+
+        check_read_into(this.read_into)?;
+        release_borrowed(this.stream, this.borrowed_len, this.borrowed_command);
+        poll_close_signal(this.stream, this.read_state, this.config, cx);
+
+        loop {
+            match mem::replace(this.read_state, ReadState::Corrupted) {
+                ReadState::Pending => {
+                    *this.read_state = ReadState::Pending;
 
-                if read.header.id == id {
-                    break read;
+                    prepare_front(
+                        this.stream,
+                        this.priority_sink,
+                        this.config,
+                        Self::MAX_READ_SIZE,
+                    )?;
+
+                    // Automatic pong replies need the whole ping command
+                    match buffered_header(this.stream, 0) {
+                        Some((header, _, _))
+                            if !(this.config.auto_pong && this.config.is_ping(&header.method)) =>
+                        {
+                            break Poll::Ready(Ok(header));
+                        }
+
+                        _ => {}
+                    }
+
+                    let other_buffered = publish_buffered(
+                        this.shared_buffer,
+                        this.stream,
+                        this.sink,
+                        this.priority_sink,
+                    );
+
+                    let read = ready!(match poll_fill(
+                        this.inner.as_mut(),
+                        cx,
+                        this.stream,
+                        this.sink.write_buffer.len()
+                            + this.priority_sink.write_buffer.len()
+                            + other_buffered,
+                        this.read_state,
+                        this.config,
+                        u64::MAX,
+                    ) {
+                        Poll::Pending => poll_read_idle(this.read_idle, this.config, cx),
+                        polled => polled,
+                    })?;
+                    if read > 0 {
+                        this.activity.record_read(this.config.clock());
+                        *this.read_idle = ReadIdle {
+                            deadline: None,
+                            sleep: None,
+                        };
+                    }
                 }
-            })
-        };
 
-        Ok(read_task)
-    }
-}
+                ReadState::PacketTooLarge => {
+                    *this.read_state = ReadState::PacketTooLarge;
 
-#[derive(Debug, Clone)]
-enum ReadState {
+                    break Poll::Ready(Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "packet is too large",
+                    )));
+                }
+
+                ReadState::Done => {
+                    *this.read_state = ReadState::Done;
+                    break Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+                }
+
+                ReadState::Closed => {
+                    *this.read_state = ReadState::Closed;
+                    break Poll::Ready(Err(LocoError::Closed.into()));
+                }
+
+                ReadState::Corrupted => break Poll::Ready(Err(LocoError::Corrupted.into())),
+            }
+        }
+    }
+
+    /// Discard the command at the front of the read buffer, reading the rest of its body
+    /// from the inner reader. Returns the number of body bytes discarded.
+    ///
+    /// Leading body bytes are copied to `prefix` up to [`Self::MAX_OVERSIZE_AUDIT_PREFIX`].
+    async fn discard_front(&mut self, mut prefix: Option<&mut Vec<u8>>) -> io::Result<u64>
+    where
+        T: Unpin,
+    {
+        release_borrowed(
+            &mut self.stream,
+            &mut self.borrowed_len,
+            &mut self.borrowed_command,
+        );
+
+        let Some((_, header_len, data_size)) = buffered_header(&self.stream, 0) else {
+            return Ok(0);
+        };
+
+        // Start over from a fresh stream, as the stream may have parsed the header already
+        let mut read_buffer = mem::take(&mut self.stream.read_buffer);
+        self.stream = LocoStream::new();
+        self.read_state = ReadState::Done;
+
+        read_buffer.drain(..header_len);
+        let buffered = read_buffer.len().min(data_size as usize);
+        if let Some(prefix) = &mut prefix {
+            prefix.extend(
+                read_buffer
+                    .iter()
+                    .take(Self::MAX_OVERSIZE_AUDIT_PREFIX.min(buffered)),
+            );
+        }
+        read_buffer.drain(..buffered);
+        self.stream.read_buffer = read_buffer;
+
+        let mut remaining = data_size as u64 - buffered as u64;
+        let mut discard = [0_u8; 4096];
+        while remaining > 0 {
+            let len = discard.len().min(remaining as usize);
+            let read =
+                poll_fn(|cx| Pin::new(&mut self.inner).poll_read(cx, &mut discard[..len])).await?;
+
+            if let Some(prefix) = &mut prefix {
+                let capture = Self::MAX_OVERSIZE_AUDIT_PREFIX.saturating_sub(prefix.len());
+                prefix.extend_from_slice(&discard[..read.min(capture)]);
+            }
+
+            if read == 0 {
+                return Err(LocoError::TruncatedPacket {
+                    expected: data_size,
+                    received: (data_size as u64 - remaining) as usize,
+                }
+                .into());
+            }
+
+            remaining -= read as u64;
+        }
+
+        self.read_state = ReadState::Pending;
+        Ok(data_size as u64)
+    }
+
+    /// Wait until a complete command is buffered, see [`Self::read_ready`]
+    pub fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        check_read_into(this.read_into)?;
+        release_borrowed(this.stream, this.borrowed_len, this.borrowed_command);
+        poll_close_signal(this.stream, this.read_state, this.config, cx);
+
+        loop {
+            match mem::replace(this.read_state, ReadState::Corrupted) {
+                ReadState::Pending => {
+                    *this.read_state = ReadState::Pending;
+
+                    prepare_front(
+                        this.stream,
+                        this.priority_sink,
+                        this.config,
+                        Self::MAX_READ_SIZE,
+                    )?;
+                    if command_ready(this.stream, 0, Self::MAX_READ_SIZE) {
+                        break Poll::Ready(Ok(()));
+                    }
+
+                    let other_buffered = publish_buffered(
+                        this.shared_buffer,
+                        this.stream,
+                        this.sink,
+                        this.priority_sink,
+                    );
+
+                    let read = ready!(match poll_fill(
+                        this.inner.as_mut(),
+                        cx,
+                        this.stream,
+                        this.sink.write_buffer.len()
+                            + this.priority_sink.write_buffer.len()
+                            + other_buffered,
+                        this.read_state,
+                        this.config,
+                        Self::MAX_READ_SIZE,
+                    ) {
+                        Poll::Pending => poll_read_idle(this.read_idle, this.config, cx),
+                        polled => polled,
+                    })?;
+                    if read > 0 {
+                        this.activity.record_read(this.config.clock());
+                        *this.read_idle = ReadIdle {
+                            deadline: None,
+                            sleep: None,
+                        };
+                    }
+                }
+
+                ReadState::PacketTooLarge => {
+                    *this.read_state = ReadState::PacketTooLarge;
+
+                    break Poll::Ready(Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "packet is too large",
+                    )));
+                }
+
+                ReadState::Done => {
+                    *this.read_state = ReadState::Done;
+                    break Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+                }
+
+                ReadState::Closed => {
+                    *this.read_state = ReadState::Closed;
+                    break Poll::Ready(Err(LocoError::Closed.into()));
+                }
+
+                ReadState::Corrupted => break Poll::Ready(Err(LocoError::Corrupted.into())),
+            }
+        }
+    }
+
+    /// Read a command appending its body to `buf` instead of the read buffer, returning
+    /// the header and where the body is within `buf`.
+    ///
+    /// Body bytes not buffered yet are read from the inner reader straight into `buf`,
+    /// so its allocation is reused across commands. `buf` may be cleared or reused freely once
+    /// this returns `Ready`. After `Pending` or an error, the next call must pass the same
+    /// buffer unchanged to continue the same command, otherwise it fails with
+    /// [`ErrorKind::InvalidInput`]. Other reads fail with [`ErrorKind::InvalidInput`] as well
+    /// until the command is done.
+    /// Bodies are returned as they arrived.
+    pub fn poll_read_into(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut Vec<u8>,
+    ) -> Poll<io::Result<(Header, Range<usize>)>> {
+        if self.read_into.is_none() {
+            if let Some(command) = self.as_mut().project().injected.pop_front() {
+                let start = buf.len();
+                buf.extend_from_slice(&command.data);
+
+                return Poll::Ready(Ok((command.header, start..buf.len())));
+            }
+
+            let header = ready!(self.as_mut().poll_header(cx))?;
+            let this = self.as_mut().project();
+
+            let Some((_, header_len, data_size)) = buffered_header(this.stream, 0) else {
+                return Poll::Ready(Err(LocoError::Corrupted.into()));
+            };
+            if data_size as u64 > Self::MAX_READ_SIZE {
+                *this.read_state = ReadState::PacketTooLarge;
+
+                return Poll::Ready(Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "packet is too large",
+                )));
+            }
+
+            // Start over from a fresh stream, as the stream may have parsed the header already
+            let mut read_buffer = mem::take(&mut this.stream.read_buffer);
+            *this.stream = LocoStream::new();
+
+            read_buffer.drain(..header_len);
+            let buffered = read_buffer.len().min(data_size as usize);
+
+            let start = buf.len();
+            buf.extend(read_buffer.drain(..buffered));
+            this.stream.read_buffer = read_buffer;
+
+            record_received(this.stats, this.replay, &header);
+            *this.read_into = Some(ReadInto {
+                header,
+                start,
+                data_size: data_size as usize,
+            });
+        }
+
+        let mut this = self.project();
+        let Some(ReadInto {
+            start, data_size, ..
+        }) = *this.read_into
+        else {
+            return Poll::Ready(Err(LocoError::Corrupted.into()));
+        };
+
+        let mut received = match buf.len().checked_sub(start) {
+            Some(received) if received <= data_size => received,
+
+            _ => {
+                return Poll::Ready(Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "buffer of a partially read command was modified",
+                )))
+            }
+        };
+        while received < data_size {
+            let end = buf.len();
+            buf.resize(end + (data_size - received).min(MIN_BODY_RESERVE), 0);
+
+            let polled = match this.inner.as_mut().poll_read(cx, &mut buf[end..]) {
+                Poll::Pending => poll_read_idle(this.read_idle, this.config, cx),
+                polled => polled,
+            };
+
+            let read = match polled {
+                Poll::Ready(Ok(0)) => {
+                    buf.truncate(end);
+                    *this.read_state = ReadState::Done;
+
+                    return Poll::Ready(Err(LocoError::TruncatedPacket {
+                        expected: data_size as u32,
+                        received,
+                    }
+                    .into()));
+                }
+
+                Poll::Ready(Ok(read)) => read,
+
+                Poll::Ready(Err(err)) => {
+                    buf.truncate(end);
+                    return Poll::Ready(Err(err));
+                }
+
+                Poll::Pending => {
+                    buf.truncate(end);
+                    return Poll::Pending;
+                }
+            };
+            buf.truncate(end + read);
+            received += read;
+
+            this.activity.record_read(this.config.clock());
+            *this.read_idle = ReadIdle {
+                deadline: None,
+                sleep: None,
+            };
+        }
+
+        let ReadInto { header, .. } = this.read_into.take().ok_or(LocoError::Corrupted)?;
+
+        Poll::Ready(Ok((header, start..start + data_size)))
+    }
+
+    /// Errors from the inner reader are not terminal. Already buffered bytes are kept
+    /// and a later call continues reading the same command.
+    ///
+    /// Commands received before one exceeding [`Self::MAX_READ_SIZE`] are all returned
+    /// in order before reads fail on the oversized one, so none of them is lost.
+    pub fn poll_read(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<BoxedCommand>> {
+        let mut this = self.project();
+
+        check_read_into(this.read_into)?;
+        release_borrowed(this.stream, this.borrowed_len, this.borrowed_command);
+        poll_close_signal(this.stream, this.read_state, this.config, cx);
+
+        if let Some(command) = this.injected.pop_front() {
+            return Poll::Ready(Ok(command));
+        }
+
+        loop {
+            match mem::replace(this.read_state, ReadState::Corrupted) {
+                ReadState::Pending => {
+                    *this.read_state = ReadState::Pending;
+
+                    // Parse again right after each fill, only going back through
+                    // the state machine once the read state changes
+                    while let ReadState::Pending = this.read_state {
+                        if let Some(command) = read_buffered(
+                            this.stream,
+                            this.parsed_header,
+                            this.priority_sink,
+                            this.config,
+                            Self::MAX_READ_SIZE,
+                        )? {
+                            publish_buffered(
+                                this.shared_buffer,
+                                this.stream,
+                                this.sink,
+                                this.priority_sink,
+                            );
+
+                            record_received(this.stats, this.replay, &command.header);
+                            return Poll::Ready(Ok(command));
+                        }
+
+                        let other_buffered = publish_buffered(
+                            this.shared_buffer,
+                            this.stream,
+                            this.sink,
+                            this.priority_sink,
+                        );
+
+                        let read = ready!(match poll_fill(
+                            this.inner.as_mut(),
+                            cx,
+                            this.stream,
+                            this.sink.write_buffer.len()
+                                + this.priority_sink.write_buffer.len()
+                                + other_buffered,
+                            this.read_state,
+                            this.config,
+                            Self::MAX_READ_SIZE,
+                        ) {
+                            Poll::Pending => poll_read_idle(this.read_idle, this.config, cx),
+                            polled => polled,
+                        })?;
+                        if read > 0 {
+                            this.activity.record_read(this.config.clock());
+                            *this.read_idle = ReadIdle {
+                                deadline: None,
+                                sleep: None,
+                            };
+                        }
+                    }
+                }
+
+                ReadState::PacketTooLarge => {
+                    *this.read_state = ReadState::PacketTooLarge;
+
+                    break Poll::Ready(Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "packet is too large",
+                    )));
+                }
+
+                ReadState::Done => {
+                    *this.read_state = ReadState::Done;
+                    break Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+                }
+
+                ReadState::Closed => {
+                    *this.read_state = ReadState::Closed;
+                    break Poll::Ready(Err(LocoError::Closed.into()));
+                }
+
+                ReadState::Corrupted => break Poll::Ready(Err(LocoError::Corrupted.into())),
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Clone> LocoClient<T> {
+    /// Write a command and wait until every buffered byte was handed to the inner writer
+    /// and the inner writer itself was flushed. Same as [`Self::send_flushed`].
+    /// With a flush window set, only flushes once the window is due.
+    pub async fn send(&mut self, method: Method, data: &[u8]) -> io::Result<u32>
+    where
+        T: Unpin,
+    {
+        if self.config.flush_window.is_none() {
+            return self.send_flushed(method, data).await;
+        }
+
+        let mut this = Pin::new(self);
+
+        let id = this.as_mut().write(method, data);
+
+        if this.flush_window_due() {
+            poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+        }
+
+        Ok(id)
+    }
+
+    /// Write a command and resolve once the inner writer's `poll_flush` completed.
+    pub async fn send_flushed(&mut self, method: Method, data: &[u8]) -> io::Result<u32>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        let id = this.as_mut().write(method, data);
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+
+        Ok(id)
+    }
+
+    /// Write a command and resolve once the inner writer accepted every buffered byte
+    /// through `poll_write`. The inner writer is not flushed, so bytes may still sit in its buffer.
+    pub async fn send_buffered(&mut self, method: Method, data: &[u8]) -> io::Result<u32>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        let id = this.as_mut().write(method, data);
+
+        poll_fn(|cx| this.as_mut().poll_write_buffer(cx)).await?;
+
+        Ok(id)
+    }
+
+    /// Send a one-way command for which no response is expected.
+    pub async fn notify(&mut self, method: Method, data: &[u8]) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        self.send(method, data).await?;
+
+        Ok(())
+    }
+
+    /// Wait until every buffered command was written and the inner writer flushed,
+    /// without closing it
+    pub async fn drain(&mut self) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await
+    }
+
+    /// [`Self::drain`], giving up after `timeout` timed with [`Self::clock`].
+    /// Returns `false` if it timed out, leaving unwritten bytes buffered and the client usable.
+    pub async fn drain_timeout(&mut self, timeout: Duration) -> io::Result<bool>
+    where
+        T: Unpin,
+    {
+        let clock = self.clock();
+        let mut sleep = clock.sleep_until(clock.now() + timeout);
+
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| {
+            if this.as_mut().poll_flush(cx)?.is_ready() {
+                return Poll::Ready(Ok(true));
+            }
+
+            sleep.as_mut().poll(cx).map(|_| Ok(false))
+        })
+        .await
+    }
+
+    /// Flush every buffered command, then close the inner writer
+    pub async fn close(&mut self) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| this.as_mut().poll_close(cx)).await
+    }
+
+    pub fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        self.project().inner.poll_close(cx)
+    }
+
+    /// Flush once the flush window is due, waiting for it on the clock.
+    /// Same as [`Self::poll_flush`] without a flush window.
+    pub fn poll_flush_window(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        if !self.flush_window_due() {
+            let this = self.as_mut().project();
+
+            if let Some(deadline) = this.flush_window.deadline {
+                let clock = this.config.clock.as_deref().unwrap_or(&SystemClock);
+                let sleep = this
+                    .flush_window
+                    .sleep
+                    .get_or_insert_with(|| clock.sleep_until(deadline));
+
+                ready!(sleep.as_mut().poll(cx));
+            }
+        }
+
+        self.poll_flush(cx)
+    }
+
+    pub fn write(mut self: Pin<&mut Self>, method: Method, data: &[u8]) -> u32 {
+        let id = {
+            let this = self.as_mut().project();
+
+            next_id(this.current_id, this.config, &method)
+        };
+        let (data_type, data) = compress_body(&self.config, data);
+
+        self.write_command(Command {
+            header: Header {
+                id,
+                status: 0,
+                method,
+                data_type,
+            },
+            data: &*data,
+        });
+
+        id
+    }
+
+    /// Write a command with the header as is, without assigning a new id
+    pub fn write_command(self: Pin<&mut Self>, command: Command<&[u8]>) {
+        let this = self.project();
+
+        if let (Some((window, _)), None) = (this.config.flush_window, this.flush_window.deadline) {
+            let clock = this.config.clock.as_deref().unwrap_or(&SystemClock);
+            this.flush_window.deadline = Some(clock.now() + window);
+        }
+
+        this.stats.record_sent(&command.header.method);
+        this.activity.record_queued(
+            this.config.clock(),
+            this.sink.write_buffer.len() + this.priority_sink.write_buffer.len(),
+        );
+
+        if let Some(replay) = this.replay {
+            replay.record(&command);
+        }
+
+        this.frames.push_back(HEADER_SIZE + command.data.len());
+        queue_command(this.sink, command);
+        update_backpressure(this.config, this.sink, this.priority_sink);
+
+        publish_buffered(
+            this.shared_buffer,
+            this.stream,
+            this.sink,
+            this.priority_sink,
+        );
+    }
+
+    /// Write a command ahead of every queued command, except a command which
+    /// is partially written already. Bytes already submitted to the inner writer cannot be preempted.
+    pub fn write_priority(mut self: Pin<&mut Self>, method: Method, data: &[u8]) -> u32 {
+        let this = self.as_mut().project();
+
+        if let (Some((window, _)), None) = (this.config.flush_window, this.flush_window.deadline) {
+            let clock = this.config.clock.as_deref().unwrap_or(&SystemClock);
+            this.flush_window.deadline = Some(clock.now() + window);
+        }
+
+        this.stats.record_sent(&method);
+        this.activity.record_queued(
+            this.config.clock(),
+            this.sink.write_buffer.len() + this.priority_sink.write_buffer.len(),
+        );
+
+        let id = next_id(this.current_id, this.config, &method);
+        let (data_type, data) = compress_body(this.config, data);
+
+        let command = Command {
+            header: Header {
+                id,
+                status: 0,
+                method,
+                data_type,
+            },
+            data: &*data,
+        };
+        if let Some(replay) = this.replay {
+            replay.record(&command);
+        }
+
+        queue_command(this.priority_sink, command);
+        update_backpressure(this.config, this.sink, this.priority_sink);
+
+        publish_buffered(
+            this.shared_buffer,
+            this.stream,
+            this.sink,
+            this.priority_sink,
+        );
+
+        id
+    }
+
+    /// Reply to a server request, echoing its id and method
+    pub async fn reply_to(&mut self, request: &BoxedCommand, data: &[u8]) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        this.as_mut().write_command(Command {
+            header: Header {
+                id: request.header.id,
+                status: 0,
+                method: request.header.method.clone(),
+                data_type: 0,
+            },
+            data,
+        });
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await
+    }
+
+    pub fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_write_buffer(cx))?;
+
+        let mut inner = self.project().inner;
+        let mut interrupted = 0;
+        loop {
+            match ready!(inner.as_mut().poll_flush(cx)) {
+                Err(err)
+                    if err.kind() == ErrorKind::Interrupted
+                        && interrupted < Self::MAX_INTERRUPTED_RETRIES =>
+                {
+                    interrupted += 1;
+                }
+
+                res => break Poll::Ready(res),
+            }
+        }
+    }
+
+    /// Hand every buffered byte to the inner writer, without flushing it
+    pub fn poll_write_buffer(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        while ready!(self.as_mut().poll_write_once(cx))? {}
+
+        *self.project().flush_window = FlushWindow {
+            deadline: None,
+            sleep: None,
+        };
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Resolves once the inner writer accepts more bytes and the write buffer is under
+    /// [`Self::with_max_total_buffer`] limit, writing buffered commands as needed.
+    /// Only buffered writes count towards the limit here, not buffered reads.
+    ///
+    /// Readiness of the inner writer can only be observed while there are bytes to write,
+    /// so this resolves immediately with an empty write buffer.
+    pub fn poll_writable(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        loop {
+            let over_limit = self
+                .config
+                .max_total_buffer
+                .is_some_and(|limit| self.pending_write_bytes() >= limit);
+
+            if !ready!(self.as_mut().poll_write_once(cx))? || !over_limit {
+                break Poll::Ready(Ok(()));
+            }
+        }
+    }
+
+    pub async fn writable(&mut self) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| this.as_mut().poll_writable(cx)).await
+    }
+
+    /// Submit buffered bytes to a single successful inner `poll_write`.
+    /// Returns `false` if there was nothing to write.
+    fn poll_write_once(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<bool>> {
+        let mut this = self.project();
+
+        let buffer = if front_is_priority(*this.frame_written, this.priority_sink) {
+            &mut this.priority_sink.write_buffer
+        } else {
+            &mut this.sink.write_buffer
+        };
+
+        if buffer.is_empty() {
+            return Poll::Ready(Ok(false));
+        }
+
+        // Small queued commands wrapping around the ring buffer are moved into one
+        // contiguous slice, so they go out in a single inner write
+        if !buffer.as_slices().1.is_empty() && buffer.len() <= MAX_COALESCE_SIZE {
+            buffer.make_contiguous();
+        }
+
+        let mut interrupted = 0;
+        let written = loop {
+            match ready!(this.inner.as_mut().poll_write(cx, {
+                let slices = buffer.as_slices();
+
+                let slice = if !slices.0.is_empty() {
+                    slices.0
+                } else {
+                    slices.1
+                };
+
+                match this.config.max_write_chunk {
+                    Some(max) => &slice[..slice.len().min(max)],
+                    None => slice,
+                }
+            })) {
+                Ok(0) if *this.zero_writes < this.config.write_zero_retries => {
+                    *this.zero_writes += 1;
+
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+
+                Ok(0) => {
+                    *this.zero_writes = 0;
+                    return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+                }
+
+                Ok(written) => {
+                    *this.zero_writes = 0;
+                    break written;
+                }
+
+                Err(err)
+                    if err.kind() == ErrorKind::Interrupted
+                        && interrupted < Self::MAX_INTERRUPTED_RETRIES =>
+                {
+                    interrupted += 1;
+                }
+
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        };
+
+        if let Some(completed) = advance_written(
+            this.sink,
+            this.frames,
+            this.frame_written,
+            this.priority_sink,
+            this.bytes_flushed_total,
+            written,
+        ) {
+            this.stats
+                .record_inner_write(completed, *this.frame_written > 0);
+        }
+        this.activity.record_write(this.config.clock());
+        update_backpressure(this.config, this.sink, this.priority_sink);
+
+        publish_buffered(
+            this.shared_buffer,
+            this.stream,
+            this.sink,
+            this.priority_sink,
+        );
+
+        Poll::Ready(Ok(true))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Clone> LocoClient<T> {
+    /// Write a request, returning a future which reads until the first command with its id.
+    /// Commands read in the meantime are discarded, and any later command with the
+    /// same id is returned by following reads like any other command.
+    pub async fn request(
+        &mut self,
+        method: Method,
+        data: &[u8],
+    ) -> io::Result<impl Future<Output = io::Result<BoxedCommand>> + '_> {
+        let mut this = Pin::new(self);
+
+        let id = this.as_mut().write(method.clone(), data);
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+
+        let read_task = async move {
+            Pin::into_inner(this)
+                .read_response(id, &method, RequestOptions::DEFAULT)
+                .await
+        };
+
+        Ok(read_task)
+    }
+
+    /// Write a request and read until the first command with its id, with opt-in
+    /// behaviors picked by `options`. [`Self::request`] uses [`RequestOptions::DEFAULT`].
+    pub async fn request_raw(
+        &mut self,
+        method: Method,
+        data: &[u8],
+        options: RequestOptions,
+    ) -> io::Result<BoxedCommand> {
+        let id = Pin::new(&mut *self).write(method.clone(), data);
+        self.drain().await?;
+
+        self.read_response(id, &method, options).await
+    }
+
+    async fn read_response(
+        &mut self,
+        id: u32,
+        method: &Method,
+        options: RequestOptions,
+    ) -> io::Result<BoxedCommand> {
+        let mut kept = VecDeque::new();
+
+        let read = loop {
+            match self.read().await {
+                Ok(read) if read.header.id == id => break Ok(read),
+
+                Ok(read) => {
+                    if options.keep_unmatched {
+                        kept.push_back(read);
+                    }
+                }
+
+                Err(err) => break Err(err),
+            }
+        };
+
+        // Kept commands go back in front of anything queued meanwhile
+        kept.append(&mut self.injected);
+        self.injected = kept;
+
+        let read = read?;
+        if let Some((_, allowed)) = self
+            .config
+            .allowed_responses
+            .iter()
+            .find(|(request, _)| request == method)
+        {
+            if !allowed.contains(&read.header.method) {
+                return Err(LocoError::UnexpectedResponseMethod {
+                    request: method.clone(),
+                    response: read.header.method,
+                    id,
+                }
+                .into());
+            }
+        }
+
+        if options.check_status {
+            if let Some(err) = Status(read.header.status).as_error() {
+                return Err(err.into());
+            }
+        }
+
+        Ok(read)
+    }
+
+    /// Send a request and return the very next command read, whatever its id.
+    ///
+    /// Only for lockstep servers which always answer a request before sending anything
+    /// else, as pushes or responses to earlier requests would be returned instead.
+    pub async fn exchange(&mut self, method: Method, data: &[u8]) -> io::Result<BoxedCommand> {
+        self.send_flushed(method, data).await?;
+
+        self.read().await
+    }
+
+    /// Send a request and measure its round trip with [`Self::clock`].
+    ///
+    /// Already buffered commands are flushed first, so the measured time spans from
+    /// handing this request to the inner writer until its response is read.
+    pub async fn request_timed(
+        &mut self,
+        method: Method,
+        data: &[u8],
+    ) -> io::Result<(BoxedCommand, Duration)> {
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+
+        let id = this.as_mut().write(method, data);
+        let start = this.clock().now();
+
+        poll_fn(|cx| this.as_mut().poll_flush(cx)).await?;
+
+        loop {
+            let read = poll_fn(|cx| this.as_mut().poll_read(cx)).await?;
+
+            if read.header.id == id {
+                let elapsed =
+                    clock::elapsed_since(this.clock(), start, this.config.clock_skew.as_ref());
+
+                break Ok((read, elapsed));
+            }
+        }
+    }
+
+    /// Send a request, reissuing it with a fresh id when an attempt fails with
+    /// [`ErrorKind::TimedOut`], [`ErrorKind::Interrupted`] or [`ErrorKind::WouldBlock`].
+    ///
+    /// Makes up to `attempts` attempts, waiting `backoff` between them. Unless `idempotent`
+    /// is `true` the request is never reissued, as the server may have handled a failed attempt.
+    pub async fn request_retry(
+        &mut self,
+        method: Method,
+        data: &[u8],
+        attempts: usize,
+        backoff: Duration,
+        idempotent: bool,
+    ) -> io::Result<BoxedCommand> {
+        let attempts = if idempotent { attempts.max(1) } else { 1 };
+
+        let mut attempt = 1;
+        loop {
+            let res = match self.request(method.clone(), data).await {
+                Ok(response) => response.await,
+                Err(err) => Err(err),
+            };
+
+            match res {
+                Err(err)
+                    if attempt < attempts
+                        && matches!(
+                            err.kind(),
+                            ErrorKind::TimedOut | ErrorKind::Interrupted | ErrorKind::WouldBlock
+                        ) =>
+                {
+                    attempt += 1;
+
+                    let clock = self.clock();
+                    clock.sleep_until(clock.now() + backoff).await;
+                }
+
+                res => break res,
+            }
+        }
+    }
+}
+
+/// Opt-in behaviors of [`LocoClient::request_raw`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RequestOptions {
+    /// Fail with [`LocoError::Status`] if the response status is not [`Status::SUCCESS`]
+    pub check_status: bool,
+
+    /// Keep commands read while waiting for the response, so following reads return them
+    /// in order instead of discarding them. They are lost if the request is cancelled.
+    pub keep_unmatched: bool,
+}
+
+impl RequestOptions {
+    pub const DEFAULT: Self = Self {
+        check_status: false,
+        keep_unmatched: false,
+    };
+}
+
+/// Spare read buffer space from [`LocoClient::reserve_read`]
+#[derive(Debug)]
+pub struct ReadReservation<'a> {
+    read_buffer: &'a mut VecDeque<u8>,
+    start: usize,
+    filled: usize,
+}
+
+impl ReadReservation<'_> {
+    /// Reserved space to fill. It can be shorter than requested if the read buffer
+    /// wraps around, the rest is discarded once filled.
+    pub fn buf(&mut self) -> &mut [u8] {
+        read_buffer_tail(self.read_buffer, self.start)
+    }
+
+    /// Keep the first `n` bytes of [`Self::buf`] as read
+    ///
+    /// # Panics
+    /// Panics if `n` is larger than [`Self::buf`].
+    pub fn fill_read(mut self, n: usize) {
+        assert!(n <= self.buf().len(), "filled past the reserved space");
+
+        self.filled = n;
+    }
+}
+
+impl Drop for ReadReservation<'_> {
+    fn drop(&mut self) {
+        self.read_buffer.truncate(self.start + self.filled);
+    }
+}
+
+/// Received command with the exact bytes it arrived as
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawCommand {
+    header: Header,
+    bytes: Box<[u8]>,
+}
+
+impl RawCommand {
+    pub const fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.bytes[HEADER_SIZE..]
+    }
+
+    /// Header and body bytes as received
+    pub const fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_command(self) -> BoxedCommand {
+        BoxedCommand {
+            data: self.data().into(),
+            header: self.header,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    Response,
+    ServerRequest,
+    Push,
+}
+
+/// What to do when the next header does not look like a valid LOCO header,
+/// which usually means framing got misaligned.
+///
+/// The check is a heuristic and can reject headers a server legitimately sends.
+/// [`ResyncMode::Scan`] may also lock onto bytes inside a body that happen to look like a header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResyncMode {
+    /// Parse every header as is
+    #[default]
+    Off,
+
+    /// Fail with [`LocoError::Desynchronized`] carrying the offending header bytes
+    Bail,
+
+    /// Discard bytes one at a time until a plausible header is found
+    Scan,
+}
+
+/// How strictly method fields of received headers are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MethodValidation {
+    /// Parse method fields as is, only failing with [`LocoError::InvalidMethod`]
+    /// if they are not UTF-8
+    #[default]
+    Lenient,
+
+    /// Fail with [`LocoError::InvalidMethod`] unless the method is 1 to 11 bytes of UTF-8
+    /// without nul bytes, padded with nul bytes only
+    Strict,
+}
+
+const HEADER_SIZE: usize = 22;
+
+#[derive(Serialize, Deserialize)]
+struct FrozenState {
+    version: u8,
+    current_id: u32,
+    bytes_flushed_total: u64,
+
+    write_buffer: Vec<u8>,
+    frames: Vec<u64>,
+    frame_written: u64,
+    priority_buffer: Vec<u8>,
+
+    read_buffer: Vec<u8>,
+    read_state: FrozenReadState,
+}
+
+impl FrozenState {
+    const VERSION: u8 = 1;
+}
+
+#[derive(Serialize, Deserialize)]
+enum FrozenReadState {
+    Pending,
+    PacketTooLarge,
+    Done,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    clock: Option<Arc<dyn Clock>>,
+    clock_skew: Option<SkewReporter>,
+    auto_pong: bool,
+    heartbeat_methods: Option<(Method, Method)>,
+    flush_window: Option<(Duration, usize)>,
+    read_idle_timeout: Option<Duration>,
+    close_signal: Option<CloseSignal>,
+    context: Option<Arc<dyn Any + Send + Sync>>,
+    max_total_buffer: Option<usize>,
+    max_write_chunk: Option<usize>,
+    write_zero_retries: usize,
+    id_namespaces: Option<IdNamespaces>,
+    backpressure: Option<Backpressure>,
+    resync: ResyncMode,
+    message_oriented: bool,
+    method_validation: MethodValidation,
+    decompressors: Vec<(u8, Arc<dyn Decompressor>)>,
+    oversize_audit: Option<OversizeAudit>,
+    allowed_responses: Vec<(Method, Vec<Method>)>,
+    compression: Option<(CompressionPolicy, Arc<dyn Compressor>)>,
+    health_thresholds: HealthThresholds,
+}
+
+impl Config {
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_deref().unwrap_or(&SystemClock)
+    }
+
+    fn is_ping(&self, method: &Method) -> bool {
+        match &self.heartbeat_methods {
+            Some((ping, _)) => ping == method,
+            None => methods::PING == *method,
+        }
+    }
+
+    fn pong_method(&self, ping: Method) -> Method {
+        match &self.heartbeat_methods {
+            Some((_, pong)) => pong.clone(),
+            None => ping,
+        }
+    }
+
+    const fn new() -> Self {
+        Self {
+            clock: None,
+            clock_skew: None,
+            auto_pong: false,
+            heartbeat_methods: None,
+            flush_window: None,
+            read_idle_timeout: None,
+            close_signal: None,
+            context: None,
+            max_total_buffer: None,
+            max_write_chunk: None,
+            write_zero_retries: 0,
+            id_namespaces: None,
+            backpressure: None,
+            resync: ResyncMode::Off,
+            message_oriented: false,
+            method_validation: MethodValidation::Lenient,
+            decompressors: Vec::new(),
+            oversize_audit: None,
+            allowed_responses: Vec::new(),
+            compression: None,
+            health_thresholds: HealthThresholds::DEFAULT,
+        }
+    }
+}
+
+type OversizeAuditFn = dyn FnMut(&Header, &[u8]) + Send;
+
+#[derive(Clone)]
+struct OversizeAudit(Arc<Mutex<OversizeAuditFn>>);
+
+impl Debug for OversizeAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OversizeAudit").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct ReadInto {
+    header: Header,
+    start: usize,
+    data_size: usize,
+}
+
+struct FlushWindow {
+    deadline: Option<Instant>,
+    sleep: Option<Sleep>,
+}
+
+impl Debug for FlushWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlushWindow")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+struct ReadIdle {
+    deadline: Option<Instant>,
+    sleep: Option<Sleep>,
+}
+
+impl Debug for ReadIdle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadIdle")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+const READ_CHUNK_SIZE: usize = 1024;
+const MIN_BODY_RESERVE: usize = 64 * 1024;
+const MAX_COALESCE_SIZE: usize = 64 * 1024;
+
+/// Buffer usage of both halves of a split client, so the total buffer limit
+/// applies to the whole connection
+#[derive(Debug)]
+struct SharedBuffer {
+    total: Arc<AtomicU64>,
+    published: u64,
+}
+
+impl SharedBuffer {
+    fn pair() -> (Self, Self) {
+        let total = Arc::new(AtomicU64::new(0));
+
+        (
+            Self {
+                total: total.clone(),
+                published: 0,
+            },
+            Self {
+                total,
+                published: 0,
+            },
+        )
+    }
+
+    /// Publish buffer usage of this half, returning usage of the other half
+    fn publish(&mut self, usage: u64) -> u64 {
+        let total = if usage >= self.published {
+            let added = usage - self.published;
+            self.total.fetch_add(added, Ordering::AcqRel) + added
+        } else {
+            let removed = self.published - usage;
+            self.total.fetch_sub(removed, Ordering::AcqRel) - removed
+        };
+        self.published = usage;
+
+        total - usage
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        self.total.fetch_sub(self.published, Ordering::AcqRel);
+    }
+}
+
+fn publish_buffered(
+    shared_buffer: &mut Option<SharedBuffer>,
+    stream: &LocoStream,
+    sink: &LocoSink,
+    priority_sink: &LocoSink,
+) -> usize {
+    let Some(shared_buffer) = shared_buffer else {
+        return 0;
+    };
+
+    let usage =
+        stream.read_buffer.len() + sink.write_buffer.len() + priority_sink.write_buffer.len();
+
+    shared_buffer.publish(usage as u64) as usize
+}
+
+/// Read once from inner reader into the read buffer, moving `read_state` to
+/// [`ReadState::PacketTooLarge`] or [`ReadState::Done`] when it applies.
+/// Returns the number of bytes read.
+fn poll_fill<T: AsyncRead>(
+    mut inner: Pin<&mut T>,
+    cx: &mut Context,
+    stream: &mut LocoStream,
+    write_buffered: usize,
+    read_state: &mut ReadState,
+    config: &Config,
+    max_size: u64,
+) -> Poll<io::Result<usize>> {
+    if let Some((_, _, data_size)) = buffered_header(stream, 0) {
+        if data_size as u64 > max_size {
+            *read_state = ReadState::PacketTooLarge;
+            return Poll::Ready(Ok(0));
+        }
+    }
+
+    if let Some(limit) = config.max_total_buffer {
+        let pending = match stream.state() {
+            StreamState::Header(header) => header.data_size as usize,
+            _ => HEADER_SIZE,
+        };
+        let buffered = stream.read_buffer.len() + write_buffered;
+
+        if buffered >= limit || pending + write_buffered > limit {
+            return Poll::Ready(Err(
+                LocoError::BufferLimitExceeded { buffered, limit }.into()
+            ));
+        }
+    }
+
+    let mut chunk_size = READ_CHUNK_SIZE;
+
+    // Read up to the rest of an announced body at once, so it does not take a read per
+    // chunk. At most doubling bytes actually received, so a peer cannot force a huge
+    // allocation without sending the body. Message oriented transports are capped the same,
+    // keeping the rest of a larger message for the next read.
+    if let StreamState::Header(header) = stream.state() {
+        let buffered = stream.read_buffer.len();
+        let missing = (header.data_size as usize).saturating_sub(buffered);
+
+        chunk_size = chunk_size.max(missing.min(buffered.max(MIN_BODY_RESERVE)));
+    }
+
+    // Read straight into the tail of the read buffer. The grown region is
+    // trimmed back to the bytes actually read before returning.
+    let start = stream.read_buffer.len();
+    stream.read_buffer.resize(start + chunk_size, 0);
+
+    let mut interrupted = 0;
+    let polled = loop {
+        match inner
+            .as_mut()
+            .poll_read(cx, read_buffer_tail(&mut stream.read_buffer, start))
+        {
+            Poll::Ready(Err(err))
+                if err.kind() == ErrorKind::Interrupted
+                    && interrupted < LocoClient::<()>::MAX_INTERRUPTED_RETRIES =>
+            {
+                interrupted += 1;
+            }
+
+            polled => break polled,
+        }
+    };
+
+    let read = match polled {
+        Poll::Ready(Ok(read)) => read,
+
+        // Partially buffered command and parse state are kept as is,
+        // so polling again resumes the same command
+        Poll::Ready(Err(err)) => {
+            stream.read_buffer.truncate(start);
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Pending => {
+            stream.read_buffer.truncate(start);
+            return Poll::Pending;
+        }
+    };
+    stream.read_buffer.truncate(start + read);
+
+    if config.message_oriented && read > 0 {
+        if let Some(received) = split_header_len(stream) {
+            return Poll::Ready(Err(LocoError::SplitHeader { received }.into()));
+        }
+    }
+
+    if read == 0 {
+        *read_state = ReadState::Done;
+
+        if let StreamState::Header(header) = stream.state() {
+            return Poll::Ready(Err(LocoError::TruncatedPacket {
+                expected: header.data_size,
+                received: stream.read_buffer.len(),
+            }
+            .into()));
+        }
+    }
+
+    Poll::Ready(Ok(read))
+}
+
+/// Length of a command header left incomplete at the end of the read buffer
+fn split_header_len(stream: &LocoStream) -> Option<usize> {
+    let mut offset = match stream.state() {
+        StreamState::Header(header) => header.data_size as usize,
+        _ => 0,
+    };
+
+    while let Some(raw) = peek_header_bytes(&stream.read_buffer, offset) {
+        offset += HEADER_SIZE + u32::from_le_bytes(raw[18..22].try_into().unwrap()) as usize;
+    }
+
+    match stream.read_buffer.len().checked_sub(offset)? {
+        0 => None,
+        received => Some(received),
+    }
+}
+
+/// Apply the outbound [`CompressionPolicy`], returning the `data_type` and body to send
+fn compress_body<'a>(config: &Config, data: &'a [u8]) -> (u8, Cow<'a, [u8]>) {
+    match &config.compression {
+        Some((policy, compressor)) if data.len() > policy.min_size => {
+            (policy.data_type, Cow::Owned(compressor.compress(data)))
+        }
+
+        _ => (0, Cow::Borrowed(data)),
+    }
+}
+
+fn update_backpressure(config: &mut Config, sink: &LocoSink, priority_sink: &LocoSink) {
+    if let Some(backpressure) = &mut config.backpressure {
+        backpressure.update(sink.write_buffer.len() + priority_sink.write_buffer.len());
+    }
+}
+
+fn next_id(current_id: &mut u32, config: &mut Config, method: &Method) -> u32 {
+    match &mut config.id_namespaces {
+        Some(namespaces) => namespaces.next_id(current_id, method),
+
+        None => {
+            *current_id += 1;
+            *current_id
+        }
+    }
+}
+
+/// Count a received command and drop the retained command it acknowledges
+fn record_received(stats: &mut LocoStats, replay: &mut Option<ReplayBuffer>, header: &Header) {
+    stats.record_received(&header.method);
+
+    if let Some(replay) = replay {
+        replay.acknowledge(header.id);
+    }
+}
+
+/// Fail while [`LocoClient::poll_read_into`] has not finished its command, as its body
+/// would be read as commands
+fn check_read_into(read_into: &Option<ReadInto>) -> io::Result<()> {
+    if read_into.is_some() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "command partially read by read_into must be finished first",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Drop buffered bytes and end the read side once the close signal is closed
+fn poll_close_signal(
+    stream: &mut LocoStream,
+    read_state: &mut ReadState,
+    config: &Config,
+    cx: &mut Context,
+) {
+    if let Some(signal) = &config.close_signal {
+        if signal.poll_closed(cx) {
+            *stream = LocoStream::new();
+            *read_state = ReadState::Closed;
+        }
+    }
+}
+
+/// Wait on the read idle timeout while the inner reader is pending,
+/// starting it if nothing was read since it was last reset
+fn poll_read_idle(
+    read_idle: &mut ReadIdle,
+    config: &Config,
+    cx: &mut Context,
+) -> Poll<io::Result<usize>> {
+    let Some(timeout) = config.read_idle_timeout else {
+        return Poll::Pending;
+    };
+
+    let clock = config.clock();
+    let deadline = *read_idle
+        .deadline
+        .get_or_insert_with(|| clock.now() + timeout);
+
+    ready!(read_idle
+        .sleep
+        .get_or_insert_with(|| clock.sleep_until(deadline))
+        .as_mut()
+        .poll(cx));
+
+    read_idle.sleep = None;
+    Poll::Ready(Err(io::Error::new(
+        ErrorKind::TimedOut,
+        "nothing was read for the read idle timeout",
+    )))
+}
+
+/// Priority commands go out between frames of the normal queue
+fn front_is_priority(frame_written: usize, priority_sink: &LocoSink) -> bool {
+    frame_written == 0 && !priority_sink.write_buffer.is_empty()
+}
+
+/// Remove `written` bytes handed to the inner writer from the front of the write buffers.
+/// Returns the number of completed commands of the normal queue, or `None` for a priority write.
+fn advance_written(
+    sink: &mut LocoSink,
+    frames: &mut VecDeque<usize>,
+    frame_written: &mut usize,
+    priority_sink: &mut LocoSink,
+    bytes_flushed_total: &mut u64,
+    written: usize,
+) -> Option<usize> {
+    *bytes_flushed_total += written as u64;
+
+    if front_is_priority(*frame_written, priority_sink) {
+        priority_sink.write_buffer.drain(..written);
+        return None;
+    }
+
+    sink.write_buffer.drain(..written);
+    *frame_written += written;
+
+    let mut completed = 0;
+    while let Some(&len) = frames.front() {
+        if *frame_written < len {
+            break;
+        }
+
+        *frame_written -= len;
+        frames.pop_front();
+        completed += 1;
+    }
+
+    Some(completed)
+}
+
+/// Contiguous part of the read buffer starting at `start`
+fn read_buffer_tail(read_buffer: &mut VecDeque<u8>, start: usize) -> &mut [u8] {
+    let (front, back) = read_buffer.as_mut_slices();
+
+    if front.len() > start {
+        &mut front[start..]
+    } else {
+        &mut back[start - front.len()..]
+    }
+}
+
+fn release_borrowed(
+    stream: &mut LocoStream,
+    borrowed_len: &mut usize,
+    borrowed_command: &mut Option<BoxedCommand>,
+) {
+    stream.read_buffer.drain(..mem::take(borrowed_len));
+    *borrowed_command = None;
+}
+
+/// Header of the command starting at `offset` in the read buffer, with the header size
+/// left in the buffer (`0` if the stream already parsed it) and body size
+fn buffered_header(stream: &LocoStream, offset: usize) -> Option<(Header, usize, u32)> {
+    match stream.state() {
+        StreamState::Header(header) if offset == 0 => {
+            Some((header.header.clone(), 0, header.data_size))
+        }
+
+        _ => {
+            let raw = peek_raw_header(&stream.read_buffer, offset)?;
+            Some((raw.header, HEADER_SIZE, raw.data_size))
+        }
+    }
+}
+
+fn command_ready(stream: &LocoStream, offset: usize, max_size: u64) -> bool {
+    match buffered_header(stream, offset) {
+        Some((_, header_size, data_size)) => {
+            data_size as u64 <= max_size
+                && stream.read_buffer.len() >= offset + header_size + data_size as usize
+        }
+
+        None => false,
+    }
+}
+
+/// Apply [`ResyncMode`] and answer automatic pings at the front of the read buffer
+fn prepare_front(
+    stream: &mut LocoStream,
+    sink: &mut LocoSink,
+    config: &Config,
+    max_size: u64,
+) -> io::Result<()> {
+    loop {
+        if config.resync != ResyncMode::Off && matches!(stream.state(), StreamState::Pending) {
+            while let Some(raw) = peek_header_bytes(&stream.read_buffer, 0) {
+                if plausible_header(&raw, max_size) {
+                    break;
+                }
+
+                if config.resync == ResyncMode::Bail {
+                    return Err(LocoError::Desynchronized {
+                        bytes: raw.to_vec(),
+                    }
+                    .into());
+                }
+
+                stream.read_buffer.pop_front();
+            }
+        }
+
+        // Checked before anything parses the header, as parsing panics on a method field
+        // which is not UTF-8
+        if matches!(stream.state(), StreamState::Pending) {
+            if let Some(raw) = peek_header_bytes(&stream.read_buffer, 0) {
+                check_method_field(&raw, config.method_validation)?;
+            }
+        }
+
+        if !config.auto_pong || !command_ready(stream, 0, max_size) {
+            return Ok(());
+        }
+
+        match buffered_header(stream, 0) {
+            Some((header, _, _)) if config.is_ping(&header.method) => {
+                stream.read();
+
+                sink.send(Command {
+                    header: Header {
+                        id: header.id,
+                        status: 0,
+                        method: config.pong_method(header.method),
+                        data_type: 0,
+                    },
+                    data: [],
+                });
+            }
+
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn read_buffered(
+    stream: &mut LocoStream,
+    parsed_header: &mut Option<[u8; HEADER_SIZE]>,
+    sink: &mut LocoSink,
+    config: &Config,
+    max_size: u64,
+) -> io::Result<Option<BoxedCommand>> {
+    prepare_front(stream, sink, config, max_size)?;
+
+    if let Some((_, _, data_size)) = buffered_header(stream, 0) {
+        if data_size as u64 > max_size {
+            return Ok(None);
+        }
+    }
+
+    // The stream consumes the header once parsed, even without the body
+    if matches!(stream.state(), StreamState::Pending) {
+        *parsed_header = peek_header_bytes(&stream.read_buffer, 0);
+    }
+
+    let Some(mut command) = stream.read() else {
+        return Ok(None);
+    };
+
+    if let Some((_, decompressor)) = config
+        .decompressors
+        .iter()
+        .find(|(data_type, _)| *data_type == command.header.data_type)
+    {
+        command.data = decompressor.decompress(&command.data)?;
+    }
+
+    Ok(Some(command))
+}
+
+/// Append `command` to the write buffer with a single reservation,
+/// instead of growing it field by field while encoding the header
+fn queue_command(sink: &mut LocoSink, command: Command<&[u8]>) {
+    let header = encode_raw_header(&RawHeader {
+        header: command.header,
+        data_size: command.data.len() as u32,
+    });
+
+    sink.write_buffer.reserve(HEADER_SIZE + command.data.len());
+    sink.write_buffer.extend(header);
+    sink.write_buffer.extend(command.data);
+}
+
+fn encode_raw_header(header: &RawHeader) -> [u8; HEADER_SIZE] {
+    let mut raw = [0_u8; HEADER_SIZE];
+    bincode::serialize_into(&mut raw[..], header).unwrap();
+
+    raw
+}
+
+fn peek_header_bytes(buffer: &VecDeque<u8>, offset: usize) -> Option<[u8; HEADER_SIZE]> {
+    if buffer.len() < offset + HEADER_SIZE {
+        return None;
+    }
+
+    let mut raw = [0_u8; HEADER_SIZE];
+    for (byte, buffered) in raw.iter_mut().zip(buffer.range(offset..)) {
+        *byte = *buffered;
+    }
+
+    Some(raw)
+}
+
+/// Check if a method field holds 1 to 11 bytes of UTF-8 without nul bytes, padded with nul bytes
+fn valid_method_field(method: &[u8]) -> bool {
+    let len = method.iter().position(|&b| b == 0).unwrap_or(method.len());
+
+    len > 0 && std::str::from_utf8(&method[..len]).is_ok() && method[len..].iter().all(|&b| b == 0)
+}
+
+/// Fail with [`LocoError::InvalidMethod`] unless the method field of `raw` passes `validation`.
+/// Method fields which are not UTF-8 are rejected even by [`MethodValidation::Lenient`].
+pub(crate) fn check_method_field(
+    raw: &[u8; HEADER_SIZE],
+    validation: MethodValidation,
+) -> Result<(), LocoError> {
+    let method = &raw[6..17];
+    let valid = match validation {
+        MethodValidation::Lenient => std::str::from_utf8(method).is_ok(),
+        MethodValidation::Strict => valid_method_field(method),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(LocoError::InvalidMethod {
+            bytes: method.to_vec(),
+        })
+    }
+}
+
+/// Heuristic used by [`ResyncMode`]: method is a non-empty run of ascii alphanumerics
+/// and underscores padded with `\0`, and body size is within `max_size`
+fn plausible_header(raw: &[u8; HEADER_SIZE], max_size: u64) -> bool {
+    let method = &raw[6..17];
+    let len = method.iter().position(|&b| b == 0).unwrap_or(method.len());
+
+    len > 0
+        && method[..len]
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        && method[len..].iter().all(|&b| b == 0)
+        && u32::from_le_bytes(raw[18..22].try_into().unwrap()) as u64 <= max_size
+}
+
+fn peek_raw_header(buffer: &VecDeque<u8>, offset: usize) -> Option<RawHeader> {
+    let raw = peek_header_bytes(buffer, offset)?;
+
+    let method = std::str::from_utf8(&raw[6..17]).ok()?;
+
+    Some(RawHeader {
+        header: Header {
+            id: u32::from_le_bytes(raw[0..4].try_into().unwrap()),
+            status: u16::from_le_bytes(raw[4..6].try_into().unwrap()),
+            method: Method::new(method.trim_end_matches('\0'))?,
+            data_type: raw[17],
+        },
+        data_size: u32::from_le_bytes(raw[18..22].try_into().unwrap()),
+    })
+}
+
+#[derive(Debug, Clone)]
+enum ReadState {
     Pending,
     PacketTooLarge,
     Done,
+    Closed,
     Corrupted,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        future::Future,
+        io::{self, ErrorKind},
+        pin::{pin, Pin},
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures_io::{AsyncRead, AsyncWrite};
+    use loco_protocol::command::Method;
+
+    use crate::{
+        clock::ManualClock,
+        close::CloseSignal,
+        error::LocoError,
+        frame::encode_command,
+        test_util::{block_on, noop_waker, ReadStep, ScriptedTransport},
+        LocoClient, MethodValidation, SharedBuffer, HEADER_SIZE,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    /// Writer failing each operation with `Interrupted` a number of times before it succeeds
+    #[derive(Debug, Clone, Default)]
+    struct InterruptedWriter {
+        interrupts: usize,
+        written: Vec<u8>,
+        flushed: bool,
+    }
+
+    impl InterruptedWriter {
+        fn interrupt(&mut self) -> bool {
+            if self.interrupts == 0 {
+                return false;
+            }
+
+            self.interrupts -= 1;
+            true
+        }
+    }
+
+    impl AsyncRead for InterruptedWriter {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(0))
+        }
+    }
+
+    impl AsyncWrite for InterruptedWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.interrupt() {
+                return Poll::Ready(Err(ErrorKind::Interrupted.into()));
+            }
+
+            self.written.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            if self.interrupt() {
+                return Poll::Ready(Err(ErrorKind::Interrupted.into()));
+            }
+
+            self.flushed = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Writer accepting zero bytes a number of times before it accepts writes
+    #[derive(Debug, Clone, Default)]
+    struct ZeroWriter {
+        zero_writes: usize,
+        written: Vec<u8>,
+    }
+
+    impl AsyncWrite for ZeroWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.zero_writes > 0 {
+                self.zero_writes -= 1;
+                return Poll::Ready(Ok(0));
+            }
+
+            self.written.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn read_error_mid_command_keeps_buffered_bytes() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let mut client = LocoClient::new(ScriptedTransport::new([
+            // Inside the header, then inside the body
+            ReadStep::Data(command[..10].to_vec()),
+            ReadStep::Error(ErrorKind::ConnectionReset),
+            ReadStep::Data(command[10..24].to_vec()),
+            ReadStep::Error(ErrorKind::ConnectionReset),
+            ReadStep::Data(command[24..].to_vec()),
+        ]));
+
+        for _ in 0..2 {
+            let err = block_on(client.read()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+        }
+
+        let read = block_on(client.read()).unwrap();
+        assert_eq!(read.header.id, 1);
+        assert_eq!(&*read.data, b"body");
+    }
+
+    #[test]
+    fn interrupted_read_is_retried() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let mut client = LocoClient::new(ScriptedTransport::new([
+            ReadStep::Error(ErrorKind::Interrupted),
+            ReadStep::Data(command[..10].to_vec()),
+            ReadStep::Error(ErrorKind::Interrupted),
+            ReadStep::Error(ErrorKind::Interrupted),
+            ReadStep::Data(command[10..].to_vec()),
+        ]));
+
+        assert_eq!(&*block_on(client.read()).unwrap().data, b"body");
+    }
+
+    #[test]
+    fn interrupted_write_and_flush_are_retried() {
+        let mut client = LocoClient::new(InterruptedWriter {
+            interrupts: 2,
+            ..Default::default()
+        });
+
+        Pin::new(&mut client).write(method("MSG"), b"body");
+        block_on(client.drain()).unwrap();
+
+        assert_eq!(client.inner().written.len(), HEADER_SIZE + 4);
+        assert!(client.inner().flushed);
+    }
+
+    #[test]
+    fn interrupted_retries_are_bounded() {
+        let mut client = LocoClient::new(InterruptedWriter {
+            interrupts: usize::MAX,
+            ..Default::default()
+        });
+
+        Pin::new(&mut client).write(method("MSG"), b"body");
+        let err = block_on(client.drain()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn eof_inside_body_is_truncated_packet() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let mut client =
+            LocoClient::new(ScriptedTransport::from_chunks(
+                [&command[..HEADER_SIZE + 2]],
+            ));
+
+        let err = block_on(client.read()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::TruncatedPacket {
+                expected: 4,
+                received: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn read_raw_keeps_header_parsed_by_unfinished_read() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let transport = ScriptedTransport::new([
+            ReadStep::Data(command[..HEADER_SIZE + 2].to_vec()),
+            ReadStep::Pending,
+        ]);
+        let mut client = LocoClient::new(transport.clone());
+
+        assert!(client.pump_reads().is_empty());
+        transport.push(ReadStep::Data(command[HEADER_SIZE + 2..].to_vec()));
+
+        let raw = block_on(client.read_raw()).unwrap();
+        assert_eq!(raw.header.id, 1);
+        assert_eq!(&*raw.bytes, &command[..]);
+    }
+
+    #[test]
+    fn writable_ignores_buffered_reads() {
+        let mut inbound = encode_command(1, method("MSG"), &[0; 200]);
+        inbound.truncate(HEADER_SIZE + 100);
+
+        let transport = ScriptedTransport::from_chunks([inbound]);
+        let mut client = LocoClient::new(transport.clone())
+            .with_max_total_buffer(64)
+            .with_max_write_chunk(4);
+
+        client.pump_reads();
+        assert!(client.stream.read_buffer.len() >= 64);
+
+        Pin::new(&mut client).write(method("MSG"), b"");
+        block_on(client.writable()).unwrap();
+
+        // Under the limit, so a single write is enough
+        assert_eq!(transport.written().len(), 4);
+    }
+
+    #[test]
+    fn shared_buffer_reports_other_half() {
+        let (mut read, mut write) = SharedBuffer::pair();
+
+        assert_eq!(read.publish(10), 0);
+        assert_eq!(write.publish(4), 10);
+        assert_eq!(read.publish(3), 4);
+
+        drop(write);
+        assert_eq!(read.publish(3), 0);
+    }
+
+    #[test]
+    fn shared_buffer_accounts_concurrent_publishes() {
+        let (read, write) = SharedBuffer::pair();
+        let total = read.total.clone();
+
+        let threads = [(read, 7), (write, 11)].map(|(mut half, step)| {
+            std::thread::spawn(move || {
+                for i in 0..10_000_u64 {
+                    half.publish(i * step % 1000);
+                }
+                half.publish(step);
+
+                half
+            })
+        });
+        let halves = threads.map(|thread| thread.join().unwrap());
+
+        assert_eq!(total.load(std::sync::atomic::Ordering::Acquire), 7 + 11);
+
+        drop(halves);
+        assert_eq!(total.load(std::sync::atomic::Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn zero_writes_are_retried_on_later_polls() {
+        let mut client = LocoClient::new(ZeroWriter {
+            zero_writes: 2,
+            ..Default::default()
+        })
+        .with_write_zero_policy(2);
+        Pin::new(&mut client).write(method("MSG"), b"data");
+
+        // Each retry yields instead of spinning within one poll
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut client).poll_flush(&mut cx).is_pending());
+        assert!(Pin::new(&mut client).poll_flush(&mut cx).is_pending());
+        assert!(matches!(
+            Pin::new(&mut client).poll_flush(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+
+        assert_eq!(
+            client.inner().written,
+            encode_command(1, method("MSG"), b"data")
+        );
+    }
+
+    #[test]
+    fn zero_write_retries_are_bounded() {
+        let mut client = LocoClient::new(ZeroWriter {
+            zero_writes: 3,
+            ..Default::default()
+        })
+        .with_write_zero_policy(2);
+        Pin::new(&mut client).write(method("MSG"), b"data");
+
+        let err = block_on(client.drain()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteZero);
+        assert!(client.inner().written.is_empty());
+    }
+
+    #[test]
+    fn fragmented_header_takes_a_read_per_fragment() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let transport = ScriptedTransport::from_chunks([
+            &command[..5],
+            &command[5..10],
+            &command[10..15],
+            &command[15..HEADER_SIZE],
+            &command[HEADER_SIZE..],
+        ]);
+        let mut client = LocoClient::new(transport.clone());
+
+        assert_eq!(&*block_on(client.read()).unwrap().data, b"body");
+        assert_eq!(transport.reads(), 5);
+    }
+
+    #[test]
+    fn large_body_is_read_in_few_chunks() {
+        let command = encode_command(1, method("MSG"), &[7; 100_000]);
+        let transport = ScriptedTransport::from_chunks([command]);
+        let mut client = LocoClient::new(transport.clone());
+
+        assert_eq!(block_on(client.read()).unwrap().data.len(), 100_000);
+
+        // The first chunk, a 64 KiB one, then the rest of the body
+        assert_eq!(transport.reads(), 3);
+    }
+
+    fn with_method_field(field: &[u8; 11]) -> Vec<u8> {
+        let mut command = encode_command(1, method("MSG"), b"body");
+        command[6..17].copy_from_slice(field);
+
+        command
+    }
+
+    fn assert_invalid_method(err: io::Error) {
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::InvalidMethod { .. })
+        ));
+    }
+
+    #[test]
+    fn non_utf8_method_fails_every_read_without_panic() {
+        let command = with_method_field(b"MS\xff\0\0\0\0\0\0\0\0");
+        let client = || LocoClient::new(ScriptedTransport::from_chunks([command.clone()]));
+
+        assert_invalid_method(block_on(client().read()).unwrap_err());
+        assert_invalid_method(block_on(client().read_raw()).unwrap_err());
+        assert_invalid_method(block_on(client().read_borrowed()).map(|_| ()).unwrap_err());
+        assert_invalid_method(block_on(client().read_filtered(|_| true)).unwrap_err());
+        assert_invalid_method(block_on(client().read_into(&mut Vec::new())).unwrap_err());
+    }
+
+    #[test]
+    fn lenient_validation_passes_utf8_garbage_through() {
+        let command = with_method_field(b"MS\0G\0\0\0\0\0\0\0");
+
+        let mut lenient = LocoClient::new(ScriptedTransport::from_chunks([command.clone()]));
+        assert_eq!(&*block_on(lenient.read()).unwrap().data, b"body");
+
+        let mut strict = LocoClient::new(ScriptedTransport::from_chunks([command]))
+            .with_method_validation(MethodValidation::Strict);
+        assert_invalid_method(block_on(strict.read()).unwrap_err());
+    }
+
+    /// Reader returning queued chunks, split if the buffer is smaller, staying pending
+    /// while none is queued
+    #[derive(Debug, Clone, Default)]
+    struct TrickleReader {
+        chunks: Arc<Mutex<VecDeque<Vec<u8>>>>,
+        buf_lens: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl AsyncRead for TrickleReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.buf_lens.lock().unwrap().push(buf.len());
+
+            let mut chunks = self.chunks.lock().unwrap();
+            let Some(mut chunk) = chunks.pop_front() else {
+                return Poll::Pending;
+            };
+
+            let read = chunk.len().min(buf.len());
+            buf[..read].copy_from_slice(&chunk[..read]);
+            if read < chunk.len() {
+                chunks.push_front(chunk.split_off(read));
+            }
+
+            Poll::Ready(Ok(read))
+        }
+    }
+
+    #[test]
+    fn read_idle_timeout_restarts_on_partial_reads() {
+        let clock = ManualClock::new();
+        let reader = TrickleReader::default();
+        let mut client = LocoClient::new(reader.clone())
+            .with_clock(Arc::new(clock.clone()))
+            .with_read_idle_timeout(Duration::from_secs(10));
+        let command = encode_command(1, method("MSG"), b"body");
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut read = pin!(client.read());
+
+        assert!(read.as_mut().poll(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(9));
+        assert!(read.as_mut().poll(&mut cx).is_pending());
+
+        // A partial header counts as progress
+        reader
+            .chunks
+            .lock()
+            .unwrap()
+            .push_back(command[..10].to_vec());
+        assert!(read.as_mut().poll(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(9));
+        assert!(read.as_mut().poll(&mut cx).is_pending());
+
+        clock.advance(Duration::from_secs(1));
+        let Poll::Ready(Err(err)) = read.as_mut().poll(&mut cx) else {
+            panic!("read did not time out");
+        };
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn close_mid_body_ends_reads() {
+        let reader = TrickleReader::default();
+        let signal = CloseSignal::new();
+        let mut client = LocoClient::new(reader.clone()).with_close_signal(signal.clone());
+        let command = encode_command(1, method("MSG"), b"body");
+        reader
+            .chunks
+            .lock()
+            .unwrap()
+            .push_back(command[..HEADER_SIZE + 2].to_vec());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(pin!(client.read()).poll(&mut cx).is_pending());
+
+        signal.close();
+        let assert_closed = |err: io::Error| {
+            assert_eq!(err.kind(), ErrorKind::ConnectionAborted);
+            assert!(matches!(LocoError::from_io(&err), Some(LocoError::Closed)));
+        };
+        assert_closed(block_on(client.read()).unwrap_err());
+
+        // Stays closed, with the partial command dropped and the rest never read
+        reader
+            .chunks
+            .lock()
+            .unwrap()
+            .push_back(command[HEADER_SIZE + 2..].to_vec());
+        assert_closed(block_on(client.read()).unwrap_err());
+        assert_closed(block_on(client.read_raw()).unwrap_err());
+        assert!(client.peek_buffered_methods().is_empty());
+        assert_eq!(reader.chunks.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn commands_before_an_oversized_one_are_returned_first() {
+        let mut burst = encode_command(1, method("MSG"), b"a");
+        burst.extend(encode_command(2, method("MSG"), b"b"));
+
+        let too_large = LocoClient::<ScriptedTransport>::MAX_READ_SIZE as u32 + 1;
+        let mut oversized = encode_command(3, method("MSG"), b"");
+        oversized[18..22].copy_from_slice(&too_large.to_le_bytes());
+        burst.extend(oversized);
+
+        let mut client = LocoClient::new(ScriptedTransport::from_chunks([burst]));
+        let reads = client.pump_reads();
+
+        assert_eq!(reads.len(), 3);
+        assert_eq!(reads[0].as_ref().unwrap().header.id, 1);
+        assert_eq!(reads[1].as_ref().unwrap().header.id, 2);
+        assert_eq!(
+            reads[2].as_ref().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn message_oriented_body_reserve_is_capped() {
+        let reader = TrickleReader::default();
+        let mut client = LocoClient::new(reader.clone()).with_message_oriented(true);
+
+        let mut header = encode_command(1, method("MSG"), b"");
+        header[18..22].copy_from_slice(&(8_u32 << 20).to_le_bytes());
+        reader.chunks.lock().unwrap().push_back(header);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(pin!(client.read()).poll(&mut cx).is_pending());
+
+        // Nothing of the 8 MiB body arrived, so far less is reserved for it
+        let buf_lens = reader.buf_lens.lock().unwrap();
+        assert_eq!(buf_lens.len(), 2);
+        assert!(buf_lens[1] <= 64 * 1024);
+    }
+
+    #[test]
+    fn oversize_audit_sees_discarded_command() {
+        let size = LocoClient::<ScriptedTransport>::MAX_READ_SIZE as usize + 1;
+        let mut inbound = encode_command(5, method("BIG"), &vec![7; size]);
+        inbound.extend(encode_command(6, method("MSG"), b"next"));
+
+        let audited = Arc::new(Mutex::new(Vec::new()));
+        let mut client = LocoClient::new(ScriptedTransport::from_chunks([inbound]))
+            .with_on_oversize({
+                let audited = audited.clone();
+                move |header, prefix| audited.lock().unwrap().push((header.id, prefix.len()))
+            });
+
+        assert!(block_on(client.read()).is_err());
+        assert_eq!(
+            block_on(client.reject_current_oversize()).unwrap(),
+            size as u64
+        );
+        assert_eq!(
+            *audited.lock().unwrap(),
+            [(
+                5,
+                LocoClient::<ScriptedTransport>::MAX_OVERSIZE_AUDIT_PREFIX
+            )]
+        );
+
+        assert_eq!(&*block_on(client.read()).unwrap().data, b"next");
+    }
+
+    #[test]
+    fn oversize_audit_is_called_after_panicking() {
+        let size = LocoClient::<ScriptedTransport>::MAX_READ_SIZE as usize + 1;
+        let inbound = [
+            encode_command(5, method("BIG"), &vec![7; size]),
+            encode_command(6, method("BIG"), &vec![7; size]),
+        ];
+
+        let audited = Arc::new(Mutex::new(Vec::new()));
+        let mut client =
+            LocoClient::new(ScriptedTransport::from_chunks(inbound)).with_on_oversize({
+                let audited = audited.clone();
+                move |header, _| {
+                    if header.id == 5 {
+                        panic!("audit failed");
+                    }
+                    audited.lock().unwrap().push(header.id);
+                }
+            });
+
+        assert!(block_on(client.read()).is_err());
+        let rejected = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            block_on(client.reject_current_oversize())
+        }));
+        assert!(rejected.is_err());
+
+        assert!(block_on(client.read()).is_err());
+        assert_eq!(
+            block_on(client.reject_current_oversize()).unwrap(),
+            size as u64
+        );
+        assert_eq!(*audited.lock().unwrap(), [6]);
+    }
+
+    #[test]
+    fn reads_fail_while_read_into_is_unfinished() {
+        let reader = TrickleReader::default();
+        let mut client = LocoClient::new(reader.clone());
+        let mut command = encode_command(1, method("MSG"), b"body");
+        command.extend(encode_command(2, method("MSG"), b"next"));
+        reader
+            .chunks
+            .lock()
+            .unwrap()
+            .push_back(command[..HEADER_SIZE + 2].to_vec());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = Vec::new();
+        assert!(pin!(client.read_into(&mut buf)).poll(&mut cx).is_pending());
+
+        reader
+            .chunks
+            .lock()
+            .unwrap()
+            .push_back(command[HEADER_SIZE + 2..].to_vec());
+        let assert_refused = |err: io::Error| assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_refused(block_on(client.read()).unwrap_err());
+        assert_refused(block_on(client.read_raw()).unwrap_err());
+        assert_refused(block_on(client.read_filtered(|_| true)).unwrap_err());
+
+        let (header, range) = block_on(client.read_into(&mut buf)).unwrap();
+        assert_eq!((header.id, &buf[range]), (1, &b"body"[..]));
+        assert_eq!(&*block_on(client.read()).unwrap().data, b"next");
+    }
+
+    #[test]
+    fn frozen_client_keeps_ids_and_buffers() {
+        let command = encode_command(7, method("MSG"), b"body");
+        let transport =
+            ScriptedTransport::new([ReadStep::Data(command[..10].to_vec()), ReadStep::Pending]);
+        let mut client = LocoClient::new(transport.clone());
+        Pin::new(&mut client).write(method("PING"), b"");
+        assert!(client.pump_reads().is_empty());
+
+        let mut client = LocoClient::thaw(transport.clone(), &client.freeze().unwrap()).unwrap();
+        Pin::new(&mut client).write(method("PING"), b"");
+        assert!(matches!(client.pump_writes(), Poll::Ready(Ok(()))));
+        transport.push(ReadStep::Data(command[10..].to_vec()));
+
+        let mut expected = encode_command(1, method("PING"), b"");
+        expected.extend(encode_command(2, method("PING"), b""));
+        assert_eq!(transport.written(), expected);
+        assert_eq!(&*client.pump_reads()[0].as_ref().unwrap().data, b"body");
+    }
+}
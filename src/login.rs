@@ -0,0 +1,273 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bson::{Bson, Document};
+use futures_core::{Future, Stream};
+
+use crate::{methods, session::LocoSession, BoxedCommand};
+
+/// Chat entry of a login list page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chat {
+    /// Chat id taken from the `c` field
+    pub id: i64,
+
+    /// Every field of the entry
+    pub data: Document,
+}
+
+type PageFuture = Pin<Box<dyn Future<Output = io::Result<BoxedCommand>> + Send>>;
+
+impl LocoSession {
+    /// Send `LOGINLIST` with `body`, yielding every chat of the `chatDatas` array of the response.
+    ///
+    /// While a page does not set `eof` to `true`, the next one is requested with `LCHATLIST`
+    /// carrying the `lastTokenId` and `lastChatId` of the previous page.
+    /// A response with a non-success status ends the stream with [`crate::error::LocoError::Status`].
+    /// A page whose `chatDatas` is not an array of chats with an integer `c` field ends it
+    /// with [`io::ErrorKind::InvalidData`].
+    pub fn login_chats(&self, body: Document) -> LoginChats {
+        LoginChats {
+            chats: VecDeque::new(),
+            next: Some(request_page(
+                self.clone(),
+                methods::LOGINLIST.method(),
+                body,
+            )),
+            session: self.clone(),
+        }
+    }
+}
+
+/// Stream returned by [`LocoSession::login_chats`]
+pub struct LoginChats {
+    chats: VecDeque<Chat>,
+    next: Option<PageFuture>,
+    session: LocoSession,
+}
+
+impl Debug for LoginChats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginChats")
+            .field("chats", &self.chats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for LoginChats {
+    type Item = io::Result<Chat>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(chat) = self.chats.pop_front() {
+                return Poll::Ready(Some(Ok(chat)));
+            }
+
+            let Some(next) = &mut self.next else {
+                return Poll::Ready(None);
+            };
+
+            let res = match next.as_mut().poll(cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.next = None;
+
+            let page = match res.and_then(|response| parse_page(&response)) {
+                Ok(page) => page,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+
+            self.chats.extend(page.chats);
+
+            if let Some(cursor) = page.cursor {
+                let session = self.session.clone();
+                self.next = Some(request_page(session, methods::LCHATLIST.method(), cursor));
+            }
+        }
+    }
+}
+
+fn request_page(
+    session: LocoSession,
+    method: loco_protocol::command::Method,
+    body: Document,
+) -> PageFuture {
+    Box::pin(async move {
+        let data =
+            bson::to_vec(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(session.request(method, data).await?.await?)
+    })
+}
+
+struct Page {
+    chats: Vec<Chat>,
+
+    /// Body requesting the next page, `None` on the last page
+    cursor: Option<Document>,
+}
+
+fn parse_page(response: &BoxedCommand) -> io::Result<Page> {
+    if let Some(err) = crate::status::Status(response.header.status).as_error() {
+        return Err(err.into());
+    }
+
+    let document = bson::from_slice::<Document>(&response.data)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let chats = match document.get("chatDatas") {
+        Some(Bson::Array(chats)) => chats.iter().map(parse_chat).collect::<io::Result<_>>()?,
+        None => Vec::new(),
+        Some(_) => return Err(invalid_page("`chatDatas` is not an array")),
+    };
+
+    let cursor = if document.get_bool("eof").unwrap_or(true) {
+        None
+    } else {
+        let mut cursor = Document::new();
+        for key in ["lastTokenId", "lastChatId"] {
+            if let Some(value) = document.get(key) {
+                cursor.insert(key, value.clone());
+            }
+        }
+
+        Some(cursor)
+    };
+
+    Ok(Page { chats, cursor })
+}
+
+fn parse_chat(chat: &Bson) -> io::Result<Chat> {
+    let data = chat
+        .as_document()
+        .ok_or_else(|| invalid_page("chat entry is not a document"))?
+        .clone();
+
+    let id = match data.get("c") {
+        Some(Bson::Int64(id)) => *id,
+        Some(Bson::Int32(id)) => i64::from(*id),
+        _ => return Err(invalid_page("chat entry has no integer `c` field")),
+    };
+
+    Ok(Chat { id, data })
+}
+
+fn invalid_page(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        pin::pin,
+        task::{Context, Poll},
+    };
+
+    use bson::{doc, Document};
+    use futures_core::Stream;
+    use loco_protocol::command::{Command, Header};
+
+    use super::parse_page;
+    use crate::{
+        frame::encode_command,
+        methods,
+        session::LocoSession,
+        test_util::{noop_waker, ReadStep, ScriptedTransport},
+        BoxedCommand, LocoClient,
+    };
+
+    fn page(document: Document) -> BoxedCommand {
+        Command {
+            header: Header {
+                id: 1,
+                status: 0,
+                method: methods::LOGINLIST.method(),
+                data_type: 0,
+            },
+            data: bson::to_vec(&document).unwrap().into(),
+        }
+    }
+
+    #[test]
+    fn pages_are_requested_until_eof() {
+        let pages = [
+            doc! {
+                "chatDatas": [{ "c": 10_i64 }, { "c": 20_i64 }],
+                "eof": false,
+                "lastTokenId": 5_i64,
+                "lastChatId": 20_i64,
+            },
+            doc! { "chatDatas": [{ "c": 30_i32 }], "eof": true },
+        ];
+        let mut responses = [methods::LOGINLIST, methods::LCHATLIST]
+            .into_iter()
+            .zip(pages)
+            .enumerate()
+            .map(|(i, (method, page))| {
+                encode_command(i as u32 + 1, method.method(), &bson::to_vec(&page).unwrap())
+            });
+
+        let transport = ScriptedTransport::default();
+        let (session, stream) = LocoSession::new(LocoClient::new(transport.clone()));
+        let mut stream = pin!(stream);
+        let mut chats = pin!(session.login_chats(doc! { "token": 1_i64 }));
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Answer each request once it is written
+        let mut written = 0;
+        let mut ids = Vec::new();
+        for _ in 0..100 {
+            match chats.as_mut().poll_next(&mut cx) {
+                Poll::Ready(Some(chat)) => ids.push(chat.unwrap().id),
+                Poll::Ready(None) => break,
+                Poll::Pending => {}
+            }
+
+            // Polls writing a request read twice
+            while transport.remaining_steps() < 2 {
+                transport.push(ReadStep::Pending);
+            }
+            assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+
+            if transport.written().len() > written {
+                written = transport.written().len();
+                transport.push(ReadStep::Data(responses.next().unwrap()));
+            }
+        }
+
+        assert_eq!(ids, [10, 20, 30]);
+        assert!(responses.next().is_none());
+
+        let cursor = doc! { "lastTokenId": 5_i64, "lastChatId": 20_i64 };
+        assert!(transport.written().ends_with(&encode_command(
+            2,
+            methods::LCHATLIST.method(),
+            &bson::to_vec(&cursor).unwrap()
+        )));
+    }
+
+    #[test]
+    fn malformed_chats_are_invalid_data() {
+        for document in [
+            doc! { "chatDatas": [{ "c": 10_i64 }, { "name": "no id" }] },
+            doc! { "chatDatas": [{ "c": "10" }] },
+            doc! { "chatDatas": [1_i32] },
+            doc! { "chatDatas": { "c": 10_i64 } },
+        ] {
+            let err = parse_page(&page(document)).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let page = parse_page(&page(doc! { "eof": true })).unwrap();
+        assert!(page.chats.is_empty() && page.cursor.is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use std::ops::Deref;
+
+use loco_protocol::command::Method;
+
+/// Method name validated at compile time, see [`loco_method!`](crate::loco_method)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodName(&'static str);
+
+impl MethodName {
+    /// Panics if `name` is empty, longer than 11 bytes or contains `\0`.
+    /// In const context the panic becomes a compile error.
+    pub const fn new(name: &'static str) -> Self {
+        let bytes = name.as_bytes();
+
+        assert!(!bytes.is_empty(), "method name is empty");
+        assert!(bytes.len() <= 11, "method name is longer than 11 bytes");
+
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(bytes[i] != 0, "method name contains nul byte");
+            i += 1;
+        }
+
+        Self(name)
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn method(&self) -> Method {
+        Method::new(self.0).unwrap()
+    }
+}
+
+impl Deref for MethodName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl From<MethodName> for Method {
+    fn from(name: MethodName) -> Self {
+        name.method()
+    }
+}
+
+impl PartialEq<Method> for MethodName {
+    fn eq(&self, other: &Method) -> bool {
+        self.0 == &**other
+    }
+}
+
+/// Create a [`MethodName`] constant, failing to compile if the name is not a valid method
+///
+/// ```
+/// use futures_loco_protocol::{loco_method, methods::MethodName};
+///
+/// const WRITE: MethodName = loco_method!("WRITE");
+/// ```
+///
+/// ```compile_fail
+/// use futures_loco_protocol::loco_method;
+///
+/// let _ = loco_method!("WAYTOOLONGNAME");
+/// ```
+#[macro_export]
+macro_rules! loco_method {
+    ($name:literal) => {{
+        const NAME: $crate::methods::MethodName = $crate::methods::MethodName::new($name);
+        NAME
+    }};
+}
+
+pub const PING: MethodName = loco_method!("PING");
+pub const CHECKIN: MethodName = loco_method!("CHECKIN");
+pub const GETCONF: MethodName = loco_method!("GETCONF");
+pub const BUYCS: MethodName = loco_method!("BUYCS");
+pub const LOGINLIST: MethodName = loco_method!("LOGINLIST");
+pub const LCHATLIST: MethodName = loco_method!("LCHATLIST");
+pub const CHANGESVR: MethodName = loco_method!("CHANGESVR");
+pub const KICKOUT: MethodName = loco_method!("KICKOUT");
+pub const WRITE: MethodName = loco_method!("WRITE");
+pub const MSG: MethodName = loco_method!("MSG");
+pub const SYNCMSG: MethodName = loco_method!("SYNCMSG");
+pub const NOTIREAD: MethodName = loco_method!("NOTIREAD");
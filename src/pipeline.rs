@@ -0,0 +1,154 @@
+use std::{collections::VecDeque, io, pin::Pin};
+
+use futures_io::{AsyncRead, AsyncWrite};
+use loco_protocol::command::{BoxedCommand, Method};
+
+use crate::{error::LocoError, LocoClient};
+
+/// Pipelined requests whose responses must arrive in the order they were written.
+///
+/// Responses are taken with [`Self::response`] in submission order. A response for any
+/// other pipelined request fails with [`LocoError::OutOfOrder`], and commands which do not
+/// answer a pipelined request are kept for [`Self::take_unrelated`].
+#[derive(Debug)]
+pub struct OrderedPipeline<T: Clone> {
+    pending: VecDeque<u32>,
+    unrelated: VecDeque<BoxedCommand>,
+    client: LocoClient<T>,
+}
+
+impl<T: Clone> OrderedPipeline<T> {
+    pub const fn new(client: LocoClient<T>) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            unrelated: VecDeque::new(),
+            client,
+        }
+    }
+
+    pub const fn client(&self) -> &LocoClient<T> {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut LocoClient<T> {
+        &mut self.client
+    }
+
+    pub fn into_client(self) -> LocoClient<T> {
+        self.client
+    }
+
+    /// Ids of requests waiting for a response, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pending.iter().copied()
+    }
+
+    /// Take the oldest command read by [`Self::response`] which did not answer a pipelined request
+    pub fn take_unrelated(&mut self) -> Option<BoxedCommand> {
+        self.unrelated.pop_front()
+    }
+}
+
+impl<T: AsyncWrite + Unpin + Clone> OrderedPipeline<T> {
+    /// Queue a request without flushing
+    pub fn write(&mut self, method: Method, data: &[u8]) -> u32 {
+        let id = Pin::new(&mut self.client).write(method, data);
+        self.pending.push_back(id);
+
+        id
+    }
+
+    /// Queue a request and flush every queued command
+    pub async fn send(&mut self, method: Method, data: &[u8]) -> io::Result<u32> {
+        let id = self.write(method, data);
+        self.client.drain().await?;
+
+        Ok(id)
+    }
+}
+
+impl<T: AsyncRead + Unpin + Clone> OrderedPipeline<T> {
+    /// Read the response of the oldest pending request.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if no request is pending.
+    /// The pending request stays queued if this fails with [`LocoError::OutOfOrder`].
+    pub async fn response(&mut self) -> io::Result<BoxedCommand> {
+        let Some(&expected) = self.pending.front() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no pipelined request is pending",
+            ));
+        };
+
+        loop {
+            let read = self.client.read().await?;
+
+            if read.header.id == expected {
+                self.pending.pop_front();
+                break Ok(read);
+            }
+
+            if let Some(index) = self.pending.iter().position(|&id| id == read.header.id) {
+                self.pending.remove(index);
+
+                break Err(LocoError::OutOfOrder {
+                    expected,
+                    received: read.header.id,
+                }
+                .into());
+            }
+
+            self.unrelated.push_back(read);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loco_protocol::command::Method;
+
+    use super::OrderedPipeline;
+    use crate::{
+        error::LocoError,
+        frame::encode_command,
+        test_util::{block_on, ScriptedTransport},
+        LocoClient,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    #[test]
+    fn out_of_order_response_fails_and_keeps_unrelated_commands() {
+        let transport = ScriptedTransport::from_chunks([
+            encode_command(0, method("KICKOUT"), b""),
+            encode_command(2, method("SECOND"), b""),
+            encode_command(1, method("FIRST"), b""),
+        ]);
+        let mut pipeline = OrderedPipeline::new(LocoClient::new(transport));
+
+        assert_eq!(pipeline.write(method("FIRST"), b""), 1);
+        assert_eq!(pipeline.write(method("SECOND"), b""), 2);
+
+        let err = block_on(pipeline.response()).unwrap_err();
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::OutOfOrder {
+                expected: 1,
+                received: 2
+            })
+        ));
+        assert_eq!(pipeline.pending().collect::<Vec<_>>(), [1]);
+
+        assert_eq!(
+            pipeline.take_unrelated().unwrap().header.method,
+            method("KICKOUT")
+        );
+        assert!(pipeline.take_unrelated().is_none());
+
+        let response = block_on(pipeline.response()).unwrap();
+        assert_eq!(response.header.id, 1);
+        assert_eq!(pipeline.pending().count(), 0);
+    }
+}
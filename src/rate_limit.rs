@@ -0,0 +1,169 @@
+use std::{
+    io,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use futures_io::AsyncWrite;
+use loco_protocol::command::Method;
+
+use crate::LocoClient;
+
+/// Token bucket limiting how many commands [`RateLimited`] writes per second
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Allow `per_second` commands per second, with up to `burst` commands at once
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        let burst = burst.max(1) as f64;
+
+        Self {
+            rate: per_second.max(1) as f64,
+            burst,
+
+            tokens: burst,
+            last_refill: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        }
+
+        self.last_refill = Some(now);
+    }
+
+    /// Take a token, returning the instant to wait until if none is available
+    fn try_take(&mut self, now: Instant) -> Result<(), Instant> {
+        self.refill(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(now + Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimited<T: Clone> {
+    bucket: TokenBucket,
+    client: LocoClient<T>,
+}
+
+impl<T: Clone> RateLimited<T> {
+    pub fn new(client: LocoClient<T>, bucket: TokenBucket) -> Self {
+        Self { bucket, client }
+    }
+
+    pub const fn client(&self) -> &LocoClient<T> {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut LocoClient<T> {
+        &mut self.client
+    }
+
+    pub fn into_client(self) -> LocoClient<T> {
+        self.client
+    }
+
+    async fn acquire(&mut self) {
+        loop {
+            let now = self.client.clock().now();
+
+            match self.bucket.try_take(now) {
+                Ok(_) => break,
+                Err(deadline) => self.client.clock().sleep_until(deadline).await,
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin + Clone> RateLimited<T> {
+    pub async fn write(&mut self, method: Method, data: &[u8]) -> u32 {
+        self.acquire().await;
+
+        Pin::new(&mut self.client).write(method, data)
+    }
+
+    pub async fn send(&mut self, method: Method, data: &[u8]) -> io::Result<u32> {
+        self.acquire().await;
+
+        self.client.send(method, data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::Context,
+        time::{Duration, Instant},
+    };
+
+    use loco_protocol::command::Method;
+
+    use super::{RateLimited, TokenBucket};
+    use crate::{
+        clock::ManualClock,
+        test_util::{block_on, noop_waker, ScriptedTransport},
+        LocoClient,
+    };
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, 3);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.try_take(start), Ok(()));
+        }
+        assert_eq!(
+            bucket.try_take(start),
+            Err(start + Duration::from_millis(500))
+        );
+
+        // Refills at the rate, never past the burst size
+        assert_eq!(bucket.try_take(start + Duration::from_millis(500)), Ok(()));
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(bucket.try_take(later), Ok(()));
+        }
+        assert!(bucket.try_take(later).is_err());
+    }
+
+    #[test]
+    fn writes_wait_for_a_token() {
+        let clock = ManualClock::new();
+        let transport = ScriptedTransport::default();
+        let client = LocoClient::new(transport).with_clock(Arc::new(clock.clone()));
+        let mut limited = RateLimited::new(client, TokenBucket::new(1, 1));
+        let method = Method::new("MSG").unwrap();
+
+        assert_eq!(block_on(limited.write(method.clone(), b"")), 1);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut write = pin!(limited.write(method, b""));
+        assert!(write.as_mut().poll(&mut cx).is_pending());
+
+        clock.advance(Duration::from_millis(999));
+        assert!(write.as_mut().poll(&mut cx).is_pending());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(write.as_mut().poll(&mut cx).is_ready());
+    }
+}
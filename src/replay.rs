@@ -0,0 +1,209 @@
+use std::{collections::VecDeque, io, pin::Pin};
+
+use futures_io::AsyncWrite;
+use loco_protocol::command::{BoxedCommand, Command, Header, Method};
+
+use crate::LocoClient;
+
+/// Recently written commands retained by [`LocoClient::with_replay_buffer`]
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayBuffer {
+    commands: VecDeque<BoxedCommand>,
+    bytes: usize,
+
+    max_commands: usize,
+    max_bytes: usize,
+}
+
+impl ReplayBuffer {
+    pub(crate) const fn new(max_commands: usize, max_bytes: usize) -> Self {
+        Self {
+            commands: VecDeque::new(),
+            bytes: 0,
+
+            max_commands,
+            max_bytes,
+        }
+    }
+
+    pub(crate) fn record(&mut self, command: &Command<&[u8]>) {
+        if command.data.len() > self.max_bytes || self.max_commands == 0 {
+            return;
+        }
+
+        while self.commands.len() >= self.max_commands
+            || self.bytes + command.data.len() > self.max_bytes
+        {
+            self.take_front();
+        }
+
+        self.bytes += command.data.len();
+        self.commands.push_back(Command {
+            header: command.header.clone(),
+            data: command.data.into(),
+        });
+    }
+
+    fn take_front(&mut self) -> Option<BoxedCommand> {
+        let command = self.commands.pop_front()?;
+        self.bytes -= command.data.len();
+
+        Some(command)
+    }
+
+    /// Drop the retained command with `id`, answered by a received command
+    pub(crate) fn acknowledge(&mut self, id: u32) {
+        self.remove(id);
+    }
+
+    fn remove(&mut self, id: u32) -> Option<BoxedCommand> {
+        let index = self
+            .commands
+            .iter()
+            .position(|command| command.header.id == id)?;
+
+        let command = self.commands.remove(index)?;
+        self.bytes -= command.data.len();
+
+        Some(command)
+    }
+}
+
+impl<T: Clone> LocoClient<T> {
+    /// Retain up to `max_commands` most recently written commands, holding at most
+    /// `max_bytes` of bodies, so they can be sent again with [`Self::resend`].
+    /// Automatic pong replies are not retained, and retained commands are dropped
+    /// once a command with the same id is received in response.
+    pub fn with_replay_buffer(mut self, max_commands: usize, max_bytes: usize) -> Self {
+        self.replay = Some(ReplayBuffer::new(max_commands, max_bytes));
+        self
+    }
+
+    /// Retained commands written after the command with `id`, oldest first.
+    /// Every retained command is returned if `id` is not retained.
+    pub fn unacked_since(&self, id: u32) -> Vec<BoxedCommand> {
+        let Some(replay) = &self.replay else {
+            return Vec::new();
+        };
+
+        let start = replay
+            .commands
+            .iter()
+            .position(|command| command.header.id == id)
+            .map_or(0, |index| index + 1);
+
+        replay.commands.range(start..).cloned().collect()
+    }
+}
+
+impl<T: AsyncWrite + Unpin + Clone> LocoClient<T> {
+    /// Write a command with the given id instead of a new one, then flush
+    pub async fn send_with_id(&mut self, id: u32, method: Method, data: &[u8]) -> io::Result<()> {
+        Pin::new(&mut *self).write_command(Command {
+            header: Header {
+                id,
+                status: 0,
+                method,
+                data_type: 0,
+            },
+            data,
+        });
+
+        self.drain().await
+    }
+
+    /// Write retained commands with `ids` again as they were first written, then flush.
+    /// Returns the number of commands written, ids which are not retained are skipped.
+    ///
+    /// Resent commands become the most recently retained ones.
+    pub async fn resend(&mut self, ids: &[u32]) -> io::Result<usize> {
+        let mut resent = 0;
+
+        for &id in ids {
+            let Some(command) = self.replay.as_mut().and_then(|replay| replay.remove(id)) else {
+                continue;
+            };
+
+            Pin::new(&mut *self).write_command(Command {
+                header: command.header,
+                data: &command.data,
+            });
+            resent += 1;
+        }
+
+        self.drain().await?;
+
+        Ok(resent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loco_protocol::command::Method;
+
+    use crate::{
+        frame::encode_command,
+        test_util::{block_on, ReadStep, ScriptedTransport},
+        LocoClient,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    fn ids(client: &LocoClient<ScriptedTransport>, since: u32) -> Vec<u32> {
+        client
+            .unacked_since(since)
+            .iter()
+            .map(|command| command.header.id)
+            .collect()
+    }
+
+    #[test]
+    fn acknowledged_commands_are_dropped() {
+        let transport = ScriptedTransport::default();
+        let mut client = LocoClient::new(transport.clone()).with_replay_buffer(8, 1024);
+        for data in [&b"first"[..], b"second", b"third"] {
+            block_on(client.send(method("MSG"), data)).unwrap();
+        }
+
+        assert_eq!(ids(&client, 0), [1, 2, 3]);
+        assert_eq!(ids(&client, 1), [2, 3]);
+        assert_eq!(&*client.unacked_since(1)[0].data, b"second");
+
+        transport.push(ReadStep::Data(encode_command(2, method("MSG"), b"")));
+        transport.push(ReadStep::Pending);
+        assert_eq!(client.pump_reads().len(), 1);
+
+        assert_eq!(ids(&client, 0), [1, 3]);
+        assert_eq!(ids(&client, 1), [3]);
+    }
+
+    #[test]
+    fn retained_commands_stay_within_budget() {
+        let mut client = LocoClient::new(ScriptedTransport::default()).with_replay_buffer(2, 8);
+        for data in [&b"1234"[..], b"5678", b"90", b"too large body"] {
+            block_on(client.send(method("MSG"), data)).unwrap();
+        }
+
+        // Four bytes fit besides the third command, the fourth exceeds the budget alone
+        assert_eq!(ids(&client, 0), [2, 3]);
+    }
+
+    #[test]
+    fn resent_commands_match_the_originals() {
+        let transport = ScriptedTransport::default();
+        let mut client = LocoClient::new(transport.clone()).with_replay_buffer(8, 1024);
+        block_on(client.send(method("MSG"), b"first")).unwrap();
+        block_on(client.send(method("WRITE"), b"second")).unwrap();
+        block_on(client.send(method("MSG"), b"third")).unwrap();
+        let first = encode_command(1, method("MSG"), b"first");
+        let third = encode_command(3, method("MSG"), b"third");
+        assert!(transport.written().starts_with(&first));
+        assert!(transport.take_written().ends_with(&third));
+
+        assert_eq!(block_on(client.resend(&[3, 9, 1])).unwrap(), 2);
+        assert_eq!(transport.take_written(), [third, first].concat());
+        assert_eq!(ids(&client, 0), [2, 3, 1]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::{self, Debug};
+
+use loco_protocol::command::Method;
+
+use crate::BoxedCommand;
+
+type RouteHandler<R> = Box<dyn FnMut(BoxedCommand) -> R + Send>;
+
+/// Table dispatching commands to handlers by method, for custom read loops.
+///
+/// ```
+/// # use futures_loco_protocol::router::Router;
+/// # use loco_protocol::command::{BoxedCommand, Header, Method};
+/// let mut router = Router::new();
+/// router.on(Method::new("MSG").unwrap(), |command| println!("{:?}", command.header));
+///
+/// let command = BoxedCommand {
+///     header: Header {
+///         id: 0,
+///         status: 0,
+///         method: Method::new("KICKOUT").unwrap(),
+///         data_type: 0,
+///     },
+///     data: Box::new([]),
+/// };
+/// assert!(router.dispatch(command).is_err());
+/// ```
+pub struct Router<R = ()> {
+    routes: Vec<(Method, RouteHandler<R>)>,
+}
+
+impl<R> Router<R> {
+    pub const fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    /// Handle commands of `method` with `handler`. The first handler registered
+    /// for a method is used if there are several.
+    pub fn on(&mut self, method: Method, handler: impl FnMut(BoxedCommand) -> R + Send + 'static) {
+        self.routes.push((method, Box::new(handler)));
+    }
+
+    pub fn handles(&self, method: &Method) -> bool {
+        self.routes.iter().any(|(route, _)| route == method)
+    }
+
+    /// Call the handler of the method of `command`, returning its output,
+    /// or give `command` back if no handler is registered for its method
+    pub fn dispatch(&mut self, command: BoxedCommand) -> Result<R, BoxedCommand> {
+        match self
+            .routes
+            .iter_mut()
+            .find(|(method, _)| *method == command.header.method)
+        {
+            Some((_, handler)) => Ok(handler(command)),
+            None => Err(command),
+        }
+    }
+}
+
+impl<R> Default for Router<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Debug for Router<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field(
+                "routes",
+                &self
+                    .routes
+                    .iter()
+                    .map(|(method, _)| &**method)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
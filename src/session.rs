@@ -1,8 +1,11 @@
 use std::{
+    collections::VecDeque,
     fmt::{self, Debug, Display},
     io, mem,
     pin::Pin,
-    task::{Context, Poll},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
 
 use flume::{r#async::RecvStream, Receiver, Sender};
@@ -11,18 +14,82 @@ use futures_io::{AsyncRead, AsyncWrite};
 use loco_protocol::command::Method;
 use nohash_hasher::IntMap;
 
-use crate::{BoxedCommand, LocoClient};
+use crate::{error::LocoError, router::Router, status::Status, BoxedCommand, LocoClient};
 
 #[derive(Debug, Clone)]
 pub struct LocoSession {
     sender: Sender<Request>,
+    waiter_sender: Sender<PushWaiter>,
+    connection_id: Arc<Mutex<Option<i64>>>,
+    error_map: Option<Arc<SessionErrorMap>>,
 }
 
 impl LocoSession {
     pub fn new<T: Clone>(client: LocoClient<T>) -> (Self, LocoSessionStream<T>) {
         let (sender, receiver) = flume::bounded(16);
+        let (waiter_sender, waiter_receiver) = flume::unbounded();
 
-        (Self { sender }, LocoSessionStream::new(receiver, client))
+        (
+            Self {
+                sender,
+                waiter_sender,
+                connection_id: Arc::new(Mutex::new(None)),
+                error_map: None,
+            },
+            LocoSessionStream::new(receiver, waiter_receiver, client),
+        )
+    }
+
+    /// Fail requests with [`Error::Server`] when their response matches `map`
+    pub fn with_error_map(mut self, map: SessionErrorMap) -> Self {
+        self.error_map = Some(Arc::new(map));
+        self
+    }
+
+    /// Server-side connection id stored by [`Self::store_connection_id`], shared by every clone
+    pub fn connection_id(&self) -> Option<i64> {
+        *self.connection_id.lock().unwrap()
+    }
+
+    /// Store the integer field `key` of a bson login response body as the connection id.
+    ///
+    /// Returns the stored id, or `None` leaving the previous one if the field is missing.
+    ///
+    /// ```
+    /// # use futures_loco_protocol::{
+    /// #     loco_protocol::command::{Command, Header, Method},
+    /// #     session::LocoSession,
+    /// #     LocoClient,
+    /// # };
+    /// let (session, _stream) = LocoSession::new(LocoClient::new(()));
+    ///
+    /// let response = Command {
+    ///     header: Header {
+    ///         id: 1,
+    ///         status: 0,
+    ///         method: Method::new("LOGINLIST").unwrap(),
+    ///         data_type: 0,
+    ///     },
+    ///     data: bson::to_vec(&bson::doc! { "userId": 1_i64, "connId": 42_i64 })
+    ///         .unwrap()
+    ///         .into(),
+    /// };
+    ///
+    /// assert_eq!(session.store_connection_id(&response, "connId"), Some(42));
+    /// assert_eq!(session.connection_id(), Some(42));
+    /// ```
+    #[cfg(feature = "bson")]
+    pub fn store_connection_id(&self, response: &BoxedCommand, key: &str) -> Option<i64> {
+        let document = bson::from_slice::<bson::Document>(&response.data).ok()?;
+
+        let id = match document.get(key)? {
+            bson::Bson::Int64(id) => *id,
+            bson::Bson::Int32(id) => i64::from(*id),
+            _ => return None,
+        };
+        *self.connection_id.lock().unwrap() = Some(id);
+
+        Some(id)
     }
 
     pub async fn request(&self, method: Method, data: Vec<u8>) -> Result<CommandRequest, Error> {
@@ -32,23 +99,114 @@ impl LocoSession {
             .send_async(Request {
                 method,
                 data,
-                response_sender: sender,
+                response_sender: Some(sender),
+                push_subscriber: None,
+            })
+            .await
+            .map_err(|_| Error::SessionClosed)?;
+
+        Ok(CommandRequest {
+            inner: receiver,
+            error_map: self.error_map.clone(),
+        })
+    }
+
+    /// Send a one-way command. No response slot is reserved, so a reply (if any) is
+    /// delivered through [`LocoSessionStream`] like any other push.
+    pub async fn notify(&self, method: Method, data: Vec<u8>) -> Result<(), Error> {
+        self.sender
+            .send_async(Request {
+                method,
+                data,
+                response_sender: None,
+                push_subscriber: None,
+            })
+            .await
+            .map_err(|_| Error::SessionClosed)
+    }
+
+    /// Wait for the next push of `method`, which is not yielded from [`LocoSessionStream`]
+    /// or given to a handler. Pushes of other methods are delivered as usual.
+    ///
+    /// Only pushes read after the stream picks up the wait are considered.
+    pub async fn await_push(&self, method: Method) -> Result<BoxedCommand, Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.waiter_sender
+            .send(PushWaiter { method, sender })
+            .map_err(|_| Error::SessionClosed)?;
+
+        receiver.await.map_err(|_| Error::SessionClosed)
+    }
+
+    /// Send a request, also returning a stream of pushes received while it is held.
+    ///
+    /// Pushes not taken by a handler are delivered to every live push stream instead of
+    /// [`LocoSessionStream`]. The push stream keeps receiving after the response arrives
+    /// until it is dropped.
+    pub async fn request_with_pushes(
+        &self,
+        method: Method,
+        data: Vec<u8>,
+    ) -> Result<(CommandRequest, PushStream), Error> {
+        let (sender, receiver) = oneshot::channel();
+        let (push_sender, push_receiver) = flume::unbounded();
+        let drained = Arc::new(Mutex::new(None));
+
+        self.sender
+            .send_async(Request {
+                method,
+                data,
+                response_sender: Some(sender),
+                push_subscriber: Some(PushSubscriber {
+                    sender: push_sender,
+                    receiver: push_receiver.clone(),
+                    drained: drained.clone(),
+                }),
             })
             .await
             .map_err(|_| Error::SessionClosed)?;
 
-        Ok(CommandRequest { inner: receiver })
+        Ok((
+            CommandRequest {
+                inner: receiver,
+                error_map: self.error_map.clone(),
+            },
+            PushStream {
+                inner: push_receiver.into_stream(),
+                drained,
+            },
+        ))
     }
 }
 
+pub type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
 pin_project_lite::pin_project!(
     pub struct LocoSessionStream<T: Clone> {
         #[pin]
         request_stream: RecvStream<'static, Request>,
 
-        response_map: IntMap<u32, oneshot::Sender<BoxedCommand>>,
+        // Senders of pending requests with the instant each was written
+        response_map: IntMap<u32, (oneshot::Sender<BoxedCommand>, Instant)>,
+
+        #[pin]
+        waiter_stream: RecvStream<'static, PushWaiter>,
+        push_waiters: Vec<PushWaiter>,
+
+        handlers: Router<HandlerFuture>,
+        running_handlers: Vec<HandlerFuture>,
+
+        push_subscribers: Vec<PushSubscriber>,
+        push_buffer_limit: Option<(usize, PushOverflowPolicy)>,
+        dropped_pushes: u64,
+
+        duplicate_policy: DuplicateResponsePolicy,
+        answered_ids: VecDeque<u32>,
 
         state: SessionState,
+        // Written requests are waiting on `poll_flush_window`
+        flushing: bool,
 
         #[pin]
         client: LocoClient<T>,
@@ -56,16 +214,89 @@ pin_project_lite::pin_project!(
 );
 
 impl<T: Clone> LocoSessionStream<T> {
-    fn new(request_receiver: Receiver<Request>, client: LocoClient<T>) -> Self {
+    fn new(
+        request_receiver: Receiver<Request>,
+        waiter_receiver: Receiver<PushWaiter>,
+        client: LocoClient<T>,
+    ) -> Self {
         Self {
             request_stream: request_receiver.into_stream(),
             response_map: IntMap::default(),
 
+            waiter_stream: waiter_receiver.into_stream(),
+            push_waiters: Vec::new(),
+
+            handlers: Router::new(),
+            running_handlers: Vec::new(),
+
+            push_subscribers: Vec::new(),
+            push_buffer_limit: None,
+            dropped_pushes: 0,
+
+            duplicate_policy: DuplicateResponsePolicy::AsPush,
+            answered_ids: VecDeque::new(),
+
             state: SessionState::Pending,
+            flushing: false,
 
             client,
         }
     }
+
+    /// Handle pushes of `method` with `handler` instead of yielding them from the stream.
+    ///
+    /// Returned futures are driven by this stream, so they can use a [`LocoSession`]
+    /// handle to send replies while the stream is polled.
+    pub fn on_async<F, Fut>(&mut self, method: Method, mut handler: F)
+    where
+        F: FnMut(BoxedCommand) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handlers
+            .on(method, move |command| Box::pin(handler(command)));
+    }
+
+    /// Limit pushes buffered by each stream of [`LocoSession::request_with_pushes`] to `limit`.
+    ///
+    /// Under [`PushOverflowPolicy::Backpressure`] commands are not read while any of them is full.
+    pub fn with_push_buffer_limit(mut self, limit: usize, policy: PushOverflowPolicy) -> Self {
+        self.push_buffer_limit = Some((limit, policy));
+        self
+    }
+
+    /// Pushes dropped by [`PushOverflowPolicy::DropOldest`] or [`PushOverflowPolicy::DropNewest`]
+    pub const fn dropped_pushes(&self) -> u64 {
+        self.dropped_pushes
+    }
+
+    /// Ids of requests written and not answered yet, in ascending order
+    pub fn pending_request_ids(&self) -> Vec<u32> {
+        let mut ids = self.response_map.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        ids
+    }
+
+    /// Longest pending request and how long ago it was written, timed with [`LocoClient::clock`]
+    pub fn oldest_pending(&self) -> Option<(u32, Duration)> {
+        let (id, (_, written)) = self
+            .response_map
+            .iter()
+            .min_by_key(|(_, (_, written))| *written)?;
+
+        Some((
+            *id,
+            self.client
+                .clock()
+                .now()
+                .saturating_duration_since(*written),
+        ))
+    }
+
+    pub fn with_duplicate_response_policy(mut self, policy: DuplicateResponsePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
 }
 
 impl<T: AsyncRead + AsyncWrite + Clone> Stream for LocoSessionStream<T> {
@@ -77,23 +308,92 @@ impl<T: AsyncRead + AsyncWrite + Clone> Stream for LocoSessionStream<T> {
         loop {
             match mem::replace(this.state, SessionState::Done) {
                 SessionState::Pending => {
-                    while let Poll::Ready(read) = this.client.as_mut().poll_read(cx) {
-                        let read = read?;
+                    while let Poll::Ready(Some(waiter)) = this.waiter_stream.as_mut().poll_next(cx)
+                    {
+                        this.push_waiters.push(waiter);
+                    }
+
+                    loop {
+                        if let Some((limit, PushOverflowPolicy::Backpressure)) =
+                            this.push_buffer_limit
+                        {
+                            if push_blocked(this.push_subscribers, *limit, cx) {
+                                break;
+                            }
+                        }
+
+                        let read = match this.client.as_mut().poll_read(cx) {
+                            Poll::Ready(Err(err))
+                                if matches!(LocoError::from_io(&err), Some(LocoError::Closed)) =>
+                            {
+                                return Poll::Ready(None);
+                            }
+
+                            Poll::Ready(read) => read?,
+                            Poll::Pending => break,
+                        };
+
+                        if let Some((sender, _)) = this.response_map.remove(&read.header.id) {
+                            if *this.duplicate_policy != DuplicateResponsePolicy::AsPush {
+                                if this.answered_ids.len() == ANSWERED_IDS_WINDOW {
+                                    this.answered_ids.pop_front();
+                                }
+                                this.answered_ids.push_back(read.header.id);
+                            }
 
-                        if let Some(sender) = this.response_map.remove(&read.header.id) {
                             let _ = sender.send(read);
-                        } else {
-                            *this.state = SessionState::Pending;
-                            return Poll::Ready(Some(Ok(read)));
+                        } else if *this.duplicate_policy != DuplicateResponsePolicy::AsPush
+                            && this.answered_ids.contains(&read.header.id)
+                        {
+                            if *this.duplicate_policy == DuplicateResponsePolicy::Error {
+                                *this.state = SessionState::Pending;
+                                return Poll::Ready(Some(Err(LocoError::DuplicateResponse {
+                                    id: read.header.id,
+                                }
+                                .into())));
+                            }
+                        } else if let Some(read) = take_push_waiter(this.push_waiters, read) {
+                            match this.handlers.dispatch(read) {
+                                Ok(handler) => this.running_handlers.push(handler),
+
+                                Err(read) if !this.push_subscribers.is_empty() => {
+                                    deliver_push(
+                                        this.push_subscribers,
+                                        *this.push_buffer_limit,
+                                        this.dropped_pushes,
+                                        &read,
+                                    );
+
+                                    if this.push_subscribers.is_empty() {
+                                        *this.state = SessionState::Pending;
+                                        return Poll::Ready(Some(Ok(read)));
+                                    }
+                                }
+
+                                Err(read) => {
+                                    *this.state = SessionState::Pending;
+                                    return Poll::Ready(Some(Ok(read)));
+                                }
+                            }
                         }
                     }
 
+                    this.running_handlers
+                        .retain_mut(|handler| handler.as_mut().poll(cx).is_pending());
+
                     let mut receiver_read = false;
                     while let Poll::Ready(Some(request)) =
                         this.request_stream.as_mut().poll_next(cx)
                     {
                         let id = this.client.as_mut().write(request.method, &request.data);
-                        this.response_map.insert(id, request.response_sender);
+                        if let Some(sender) = request.response_sender {
+                            this.response_map
+                                .insert(id, (sender, this.client.clock().now()));
+                        }
+
+                        if let Some(subscriber) = request.push_subscriber {
+                            this.push_subscribers.push(subscriber);
+                        }
 
                         if !receiver_read {
                             receiver_read = true;
@@ -101,20 +401,19 @@ impl<T: AsyncRead + AsyncWrite + Clone> Stream for LocoSessionStream<T> {
                     }
 
                     if receiver_read {
-                        *this.state = SessionState::Write;
-                    } else {
-                        *this.state = SessionState::Pending;
-                        return Poll::Pending;
+                        *this.flushing = true;
                     }
-                }
 
-                SessionState::Write => {
-                    if this.client.as_mut().poll_flush(cx)?.is_ready() {
-                        *this.state = SessionState::Pending;
-                    } else {
-                        *this.state = SessionState::Write;
+                    // Flush alongside reading, so an open flush window or a slow writer
+                    // never holds back responses and new requests
+                    if *this.flushing && this.client.as_mut().poll_flush_window(cx)?.is_ready() {
+                        *this.flushing = false;
+                    }
+
+                    *this.state = SessionState::Pending;
+                    if !receiver_read {
                         return Poll::Pending;
-                    };
+                    }
                 }
 
                 SessionState::Done => return Poll::Ready(None),
@@ -123,10 +422,27 @@ impl<T: AsyncRead + AsyncWrite + Clone> Stream for LocoSessionStream<T> {
     }
 }
 
+/// How [`LocoSessionStream`] handles a command carrying the id of a request which
+/// was answered already. The first response is always delivered to the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateResponsePolicy {
+    /// Handle duplicates like any other push
+    #[default]
+    AsPush,
+
+    /// Silently drop duplicates
+    Drop,
+
+    /// Yield [`LocoError::DuplicateResponse`] from the stream for each duplicate
+    Error,
+}
+
+/// Number of most recently answered request ids checked for duplicates
+const ANSWERED_IDS_WINDOW: usize = 64;
+
 #[derive(Debug, Clone, Copy)]
 enum SessionState {
     Pending,
-    Write,
     Done,
 }
 
@@ -134,7 +450,95 @@ enum SessionState {
 struct Request {
     method: Method,
     data: Vec<u8>,
-    response_sender: oneshot::Sender<BoxedCommand>,
+    response_sender: Option<oneshot::Sender<BoxedCommand>>,
+    push_subscriber: Option<PushSubscriber>,
+}
+
+/// What happens to a push when a push stream buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushOverflowPolicy {
+    DropOldest,
+    DropNewest,
+
+    /// Stop reading commands until the push stream catches up
+    Backpressure,
+}
+
+#[derive(Debug)]
+struct PushWaiter {
+    method: Method,
+    sender: oneshot::Sender<BoxedCommand>,
+}
+
+/// Give `push` to the oldest live waiter for its method, returning it back if there is none
+fn take_push_waiter(waiters: &mut Vec<PushWaiter>, mut push: BoxedCommand) -> Option<BoxedCommand> {
+    while let Some(index) = waiters
+        .iter()
+        .position(|waiter| waiter.method == push.header.method)
+    {
+        match waiters.remove(index).sender.send(push) {
+            Ok(()) => return None,
+            Err(err) => push = err.into_inner(),
+        }
+    }
+
+    Some(push)
+}
+
+#[derive(Debug)]
+struct PushSubscriber {
+    sender: Sender<BoxedCommand>,
+
+    // Kept to evict the oldest push, so liveness is tracked with the receiver count
+    receiver: Receiver<BoxedCommand>,
+
+    drained: Arc<Mutex<Option<Waker>>>,
+}
+
+impl PushSubscriber {
+    fn is_closed(&self) -> bool {
+        self.sender.receiver_count() <= 1
+    }
+}
+
+fn deliver_push(
+    subscribers: &mut Vec<PushSubscriber>,
+    limit: Option<(usize, PushOverflowPolicy)>,
+    dropped: &mut u64,
+    push: &BoxedCommand,
+) {
+    subscribers.retain(|subscriber| !subscriber.is_closed());
+
+    for subscriber in subscribers {
+        match limit {
+            Some((limit, PushOverflowPolicy::DropNewest)) if subscriber.sender.len() >= limit => {
+                *dropped += 1;
+                continue;
+            }
+
+            Some((limit, PushOverflowPolicy::DropOldest)) if subscriber.sender.len() >= limit => {
+                *dropped += u64::from(subscriber.receiver.try_recv().is_ok());
+            }
+
+            _ => {}
+        }
+
+        let _ = subscriber.sender.send(push.clone());
+    }
+}
+
+/// Check if any push stream is full, registering to be woken once it is drained
+fn push_blocked(subscribers: &[PushSubscriber], limit: usize, cx: &mut Context) -> bool {
+    subscribers.iter().any(|subscriber| {
+        if subscriber.is_closed() || subscriber.sender.len() < limit {
+            return false;
+        }
+
+        *subscriber.drained.lock().unwrap() = Some(cx.waker().clone());
+
+        // Recheck in case it was drained before the waker was stored
+        subscriber.sender.len() >= limit
+    })
 }
 
 pin_project_lite::pin_project! {
@@ -142,6 +546,8 @@ pin_project_lite::pin_project! {
     pub struct CommandRequest {
         #[pin]
         inner: oneshot::Receiver<BoxedCommand>,
+
+        error_map: Option<Arc<SessionErrorMap>>,
     }
 }
 
@@ -149,23 +555,575 @@ impl Future for CommandRequest {
     type Output = Result<BoxedCommand, Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let command = ready!(self
-            .project()
-            .inner
-            .poll(cx)
-            .map_err(|_| Error::SessionClosed))?;
+        let this = self.project();
+
+        let command = ready!(this.inner.poll(cx).map_err(|_| Error::SessionClosed))?;
+
+        if let Some(error) = this
+            .error_map
+            .as_deref()
+            .and_then(|map| map.classify(&command))
+        {
+            return Poll::Ready(Err(Error::Server {
+                error,
+                response: command,
+            }));
+        }
 
         Poll::Ready(Ok(command))
     }
 }
 
+pin_project_lite::pin_project! {
+    pub struct PushStream {
+        #[pin]
+        inner: RecvStream<'static, BoxedCommand>,
+
+        drained: Arc<Mutex<Option<Waker>>>,
+    }
+}
+
+impl Debug for PushStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushStream").finish_non_exhaustive()
+    }
+}
+
+impl Stream for PushStream {
+    type Item = BoxedCommand;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let next = ready!(this.inner.poll_next(cx));
+        if let Some(waker) = this.drained.lock().unwrap().take() {
+            waker.wake();
+        }
+
+        Poll::Ready(next)
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     SessionClosed,
+
+    /// Response matched an entry of the [`SessionErrorMap`] of the session
+    Server {
+        error: SessionError,
+        response: BoxedCommand,
+    },
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str("session closed")
+        match self {
+            Error::SessionClosed => f.write_str("session closed"),
+
+            Error::Server { error, response } => write!(
+                f,
+                "{error} ({}#{} answered with status {})",
+                &*response.header.method,
+                response.header.id,
+                Status(response.header.status)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match err {
+            Error::SessionClosed => io::ErrorKind::BrokenPipe,
+            Error::Server { .. } => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, err)
+    }
+}
+
+/// Server errors applications usually react to instead of failing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// Upload or storage quota exceeded
+    QuotaExceeded,
+
+    /// Too many requests, back off before retrying
+    RateLimited,
+
+    /// Session is not logged in anymore
+    ReloginRequired,
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SessionError::QuotaExceeded => "quota exceeded",
+            SessionError::RateLimited => "rate limited",
+            SessionError::ReloginRequired => "relogin required",
+        })
+    }
+}
+
+/// Response statuses mapped to [`SessionError`], as codes differ between servers.
+///
+/// The default map only holds [`Status::NOT_LOGON`] as [`SessionError::ReloginRequired`].
+///
+/// ```
+/// use futures_loco_protocol::{
+///     loco_protocol::command::Method,
+///     session::{SessionError, SessionErrorMap},
+///     status::Status,
+/// };
+///
+/// let map = SessionErrorMap::default()
+///     .with_status(Status::from_code(-805), SessionError::RateLimited)
+///     .with_method_status(
+///         Method::new("MAXUPLOAD").unwrap(),
+///         Status::OPERATION_DENIED,
+///         SessionError::QuotaExceeded,
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionErrorMap {
+    entries: Vec<(Option<Method>, Status, SessionError)>,
+}
+
+impl SessionErrorMap {
+    /// Map without any entry
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Map `status` of a response of any method to `error`
+    pub fn with_status(mut self, status: Status, error: SessionError) -> Self {
+        self.entries.push((None, status, error));
+        self
+    }
+
+    /// Map `status` of a response of `method` to `error`, taking precedence
+    /// over entries added with [`Self::with_status`]
+    pub fn with_method_status(
+        mut self,
+        method: Method,
+        status: Status,
+        error: SessionError,
+    ) -> Self {
+        self.entries.push((Some(method), status, error));
+        self
+    }
+
+    pub fn classify(&self, response: &BoxedCommand) -> Option<SessionError> {
+        let status = Status(response.header.status);
+        let mut matching = self
+            .entries
+            .iter()
+            .filter(|(_, entry_status, _)| *entry_status == status);
+
+        matching
+            .clone()
+            .find(|(method, _, _)| method.as_ref() == Some(&response.header.method))
+            .or_else(|| matching.find(|(method, _, _)| method.is_none()))
+            .map(|(_, _, error)| *error)
+    }
+}
+
+impl Default for SessionErrorMap {
+    fn default() -> Self {
+        Self::new().with_status(Status::NOT_LOGON, SessionError::ReloginRequired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::{poll_fn, Future},
+        io,
+        pin::{pin, Pin},
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures_core::Stream;
+    use loco_protocol::command::{Command, Header, Method};
+
+    use super::{
+        DuplicateResponsePolicy, LocoSession, LocoSessionStream, PushOverflowPolicy, PushStream,
+        SessionError, SessionErrorMap,
+    };
+    use crate::{
+        clock::ManualClock,
+        error::LocoError,
+        frame::encode_command,
+        status::Status,
+        test_util::{block_on, noop_waker, ReadStep, ScriptedTransport},
+        BoxedCommand, LocoClient,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    fn next_item(
+        stream: &mut Pin<&mut LocoSessionStream<ScriptedTransport>>,
+    ) -> Option<io::Result<BoxedCommand>> {
+        block_on(poll_fn(|cx| stream.as_mut().poll_next(cx)))
+    }
+
+    /// Session flooded with `count` pushes while its first request is pending, each
+    /// carrying its index as body
+    fn flooded_session(
+        count: u8,
+        limit: usize,
+        policy: PushOverflowPolicy,
+    ) -> (LocoSession, LocoSessionStream<ScriptedTransport>) {
+        let inbound = (0..count)
+            .flat_map(|i| encode_command(0, method("MSG"), &[i]))
+            .collect();
+
+        let transport = ScriptedTransport::new([ReadStep::Pending, ReadStep::Data(inbound)]);
+        let (session, stream) = LocoSession::new(LocoClient::new(transport));
+
+        (session, stream.with_push_buffer_limit(limit, policy))
+    }
+
+    fn buffered_pushes(pushes: &mut Pin<&mut PushStream>) -> Vec<u8> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut bodies = Vec::new();
+        while let Poll::Ready(Some(push)) = pushes.as_mut().poll_next(&mut cx) {
+            bodies.push(push.data[0]);
+        }
+
+        bodies
+    }
+
+    /// Session whose first request is answered twice, followed by a push
+    fn duplicated_session(
+        policy: DuplicateResponsePolicy,
+    ) -> (LocoSession, LocoSessionStream<ScriptedTransport>) {
+        let response = encode_command(1, method("GETMEM"), b"response");
+        let inbound = [
+            response.clone(),
+            response,
+            encode_command(0, method("MSG"), b"push"),
+        ]
+        .concat();
+
+        let transport = ScriptedTransport::new([ReadStep::Pending, ReadStep::Data(inbound)]);
+        let (session, stream) = LocoSession::new(LocoClient::new(transport));
+
+        (session, stream.with_duplicate_response_policy(policy))
+    }
+
+    #[test]
+    fn flush_window_does_not_block_reads() {
+        let clock = ManualClock::new();
+        let transport = ScriptedTransport::new([
+            ReadStep::Pending,
+            ReadStep::Data(encode_command(0, method("MSG"), b"push")),
+            ReadStep::Pending,
+        ]);
+        let client = LocoClient::new(transport.clone())
+            .with_clock(Arc::new(clock.clone()))
+            .with_flush_window(Duration::from_secs(10), usize::MAX);
+
+        let (session, stream) = LocoSession::new(client);
+        let mut stream = pin!(stream);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let _request = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+
+        // The request is written first, then held back by the window while reading goes on
+        let Some(Poll::Ready(Some(Ok(push)))) = (0..4)
+            .map(|_| stream.as_mut().poll_next(&mut cx))
+            .find(Poll::is_ready)
+        else {
+            panic!("push was not read while the flush window is open");
+        };
+        assert_eq!(&*push.data, b"push");
+        assert!(transport.written().is_empty());
+
+        clock.advance(Duration::from_secs(10));
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert!(!transport.written().is_empty());
+    }
+
+    #[test]
+    fn duplicate_response_as_push() {
+        let (session, stream) = duplicated_session(DuplicateResponsePolicy::AsPush);
+        let mut stream = pin!(stream);
+        let request = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+
+        let duplicate = next_item(&mut stream).unwrap().unwrap();
+        assert_eq!(
+            (duplicate.header.id, &*duplicate.data),
+            (1, &b"response"[..])
+        );
+        assert_eq!(&*block_on(request).unwrap().data, b"response");
+
+        assert_eq!(&*next_item(&mut stream).unwrap().unwrap().data, b"push");
+    }
+
+    #[test]
+    fn duplicate_response_dropped() {
+        let (session, stream) = duplicated_session(DuplicateResponsePolicy::Drop);
+        let mut stream = pin!(stream);
+        let request = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+
+        assert_eq!(&*next_item(&mut stream).unwrap().unwrap().data, b"push");
+        assert_eq!(&*block_on(request).unwrap().data, b"response");
+    }
+
+    #[test]
+    fn duplicate_response_error() {
+        let (session, stream) = duplicated_session(DuplicateResponsePolicy::Error);
+        let mut stream = pin!(stream);
+        let request = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+
+        let err = next_item(&mut stream).unwrap().unwrap_err();
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::DuplicateResponse { id: 1 })
+        ));
+        assert_eq!(&*block_on(request).unwrap().data, b"response");
+
+        // Reading goes on after the error
+        assert_eq!(&*next_item(&mut stream).unwrap().unwrap().data, b"push");
+    }
+
+    #[test]
+    fn push_flood_drops_newest() {
+        let (session, stream) = flooded_session(10, 3, PushOverflowPolicy::DropNewest);
+        let mut stream = pin!(stream);
+        let (_request, pushes) =
+            block_on(session.request_with_pushes(method("GETMEM"), Vec::new())).unwrap();
+        let mut pushes = pin!(pushes);
+
+        // Every push is read, up to the end of the stream
+        assert!(next_item(&mut stream).unwrap().is_err());
+
+        assert_eq!(buffered_pushes(&mut pushes), [0, 1, 2]);
+        assert_eq!(stream.dropped_pushes(), 7);
+    }
+
+    #[test]
+    fn push_flood_drops_oldest() {
+        let (session, stream) = flooded_session(10, 3, PushOverflowPolicy::DropOldest);
+        let mut stream = pin!(stream);
+        let (_request, pushes) =
+            block_on(session.request_with_pushes(method("GETMEM"), Vec::new())).unwrap();
+        let mut pushes = pin!(pushes);
+
+        assert!(next_item(&mut stream).unwrap().is_err());
+
+        assert_eq!(buffered_pushes(&mut pushes), [7, 8, 9]);
+        assert_eq!(stream.dropped_pushes(), 7);
+    }
+
+    #[test]
+    fn push_flood_backpressure() {
+        let (session, stream) = flooded_session(10, 3, PushOverflowPolicy::Backpressure);
+        let mut stream = pin!(stream);
+        let (_request, pushes) =
+            block_on(session.request_with_pushes(method("GETMEM"), Vec::new())).unwrap();
+        let mut pushes = pin!(pushes);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Reading stops whenever the push stream is full, until it is drained
+        let mut received = Vec::new();
+        loop {
+            let end = (0..4)
+                .map(|_| stream.as_mut().poll_next(&mut cx))
+                .find(Poll::is_ready);
+
+            let drained = buffered_pushes(&mut pushes);
+            assert!(drained.len() <= 3);
+            received.extend(drained);
+
+            if let Some(end) = end {
+                assert!(matches!(end, Poll::Ready(Some(Err(_)))));
+                break;
+            }
+        }
+
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(stream.dropped_pushes(), 0);
+    }
+
+    #[test]
+    fn async_handler_runs_around_the_stream() {
+        let inbound = [
+            encode_command(0, method("KICK"), b"reason"),
+            encode_command(0, method("MSG"), b"push"),
+        ]
+        .concat();
+        let transport = ScriptedTransport::new([
+            ReadStep::Pending,
+            ReadStep::Data(inbound),
+            ReadStep::Pending,
+            ReadStep::Pending,
+        ]);
+        let (session, mut stream) = LocoSession::new(LocoClient::new(transport.clone()));
+
+        stream.on_async(method("KICK"), move |command| {
+            let session = session.clone();
+
+            async move {
+                session
+                    .notify(method("KICKACK"), command.data.into())
+                    .await
+                    .unwrap();
+            }
+        });
+        let mut stream = pin!(stream);
+
+        // The stream yields other pushes while the handler takes its own
+        assert_eq!(&*next_item(&mut stream).unwrap().unwrap().data, b"push");
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert_eq!(
+            transport.written(),
+            encode_command(1, method("KICKACK"), b"reason")
+        );
+    }
+
+    #[test]
+    fn await_push_waits_for_its_method() {
+        let inbound = [
+            encode_command(0, method("MSG"), b"push"),
+            encode_command(1, method("GETMEM"), b"response"),
+            encode_command(0, method("KICK"), b"reason"),
+        ]
+        .concat();
+        let transport = ScriptedTransport::new([
+            ReadStep::Pending,
+            ReadStep::Data(inbound),
+            ReadStep::Pending,
+        ]);
+        let (session, stream) = LocoSession::new(LocoClient::new(transport));
+        let mut stream = pin!(stream);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let request = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+        let mut kick = pin!(session.await_push(method("KICK")));
+        let mut other = pin!(session.await_push(method("CHANGESVR")));
+        assert!(kick.as_mut().poll(&mut cx).is_pending());
+        assert!(other.as_mut().poll(&mut cx).is_pending());
+
+        // Pushes of other methods are still delivered by the stream
+        assert_eq!(&*next_item(&mut stream).unwrap().unwrap().data, b"push");
+        assert!(kick.as_mut().poll(&mut cx).is_pending());
+
+        // The response goes to its request, the matching push to the waiter only
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert_eq!(&*block_on(request).unwrap().data, b"response");
+        let Poll::Ready(Ok(push)) = kick.as_mut().poll(&mut cx) else {
+            panic!("matching push did not resolve the wait");
+        };
+        assert_eq!(&*push.data, b"reason");
+        assert!(other.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn pending_requests_age_until_answered() {
+        let clock = ManualClock::new();
+        // Each poll writing a request reads again afterwards
+        let transport = ScriptedTransport::new(vec![ReadStep::Pending; 4]);
+        let client = LocoClient::new(transport.clone()).with_clock(Arc::new(clock.clone()));
+        let (session, stream) = LocoSession::new(client);
+        let mut stream = pin!(stream);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let first = block_on(session.request(method("GETMEM"), Vec::new())).unwrap();
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(3));
+
+        let second = block_on(session.request(method("SYNCMSG"), Vec::new())).unwrap();
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(stream.pending_request_ids(), [1, 2]);
+        assert_eq!(stream.oldest_pending(), Some((1, Duration::from_secs(5))));
+
+        transport.push(ReadStep::Data(encode_command(1, method("GETMEM"), b"")));
+        transport.push(ReadStep::Pending);
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert!(block_on(first).is_ok());
+
+        assert_eq!(stream.pending_request_ids(), [2]);
+        assert_eq!(stream.oldest_pending(), Some((2, Duration::from_secs(2))));
+
+        transport.push(ReadStep::Data(encode_command(2, method("SYNCMSG"), b"")));
+        transport.push(ReadStep::Pending);
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert!(block_on(second).is_ok());
+
+        assert!(stream.pending_request_ids().is_empty());
+        assert_eq!(stream.oldest_pending(), None);
+    }
+
+    fn response(name: &str, status: Status) -> BoxedCommand {
+        Command {
+            header: Header {
+                id: 1,
+                status: status.0,
+                method: method(name),
+                data_type: 0,
+            },
+            data: Box::new([]),
+        }
+    }
+
+    #[test]
+    fn error_map_defaults_to_relogin() {
+        let map = SessionErrorMap::default();
+
+        assert_eq!(
+            map.classify(&response("GETMEM", Status::NOT_LOGON)),
+            Some(SessionError::ReloginRequired)
+        );
+        assert_eq!(map.classify(&response("GETMEM", Status::SUCCESS)), None);
+        assert_eq!(
+            map.classify(&response("GETMEM", Status::OPERATION_DENIED)),
+            None
+        );
+    }
+
+    #[test]
+    fn method_status_overrides_any_method_status() {
+        let map = SessionErrorMap::new()
+            .with_status(Status::OPERATION_DENIED, SessionError::RateLimited)
+            .with_method_status(
+                method("MAXUPLOAD"),
+                Status::OPERATION_DENIED,
+                SessionError::QuotaExceeded,
+            );
+
+        assert_eq!(
+            map.classify(&response("MAXUPLOAD", Status::OPERATION_DENIED)),
+            Some(SessionError::QuotaExceeded)
+        );
+        assert_eq!(
+            map.classify(&response("WRITE", Status::OPERATION_DENIED)),
+            Some(SessionError::RateLimited)
+        );
+        assert_eq!(map.classify(&response("MAXUPLOAD", Status::SUCCESS)), None);
     }
 }
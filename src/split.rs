@@ -0,0 +1,820 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Debug, Display},
+    future::poll_fn,
+    io::{self, ErrorKind},
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_sink::Sink;
+use loco_protocol::command::{client::LocoStream, BoxedCommand, Method};
+
+use crate::{
+    error::LocoError, publish_buffered, FlushWindow, LocoClient, ReadIdle, ReadState, SharedBuffer,
+};
+
+impl<T: Clone> LocoClient<T> {
+    /// Split into a read half and a write half, each owning a clone of the inner transport.
+    ///
+    /// Automatic pong replies queued by the read half are written by the read half while the
+    /// write half has no partially written command, and by the write half otherwise.
+    pub fn split(self) -> (LocoReadHalf<T>, LocoWriteHalf<T>) {
+        let (read, write, _) = self.split_with_shutdown();
+
+        (read, write)
+    }
+
+    /// [`Self::split`], also returning a [`Shutdown`] handle to shut both halves down
+    pub fn split_with_shutdown(mut self) -> (LocoReadHalf<T>, LocoWriteHalf<T>, Shutdown) {
+        let shutdown = Arc::new(Mutex::new(ShutdownState::default()));
+        let pongs = Arc::new(Mutex::new(PongQueue {
+            bytes: VecDeque::new(),
+            writer_idle: self.frame_written == 0 && self.priority_sink.write_buffer.is_empty(),
+        }));
+        let (read_buffer, write_buffer) = SharedBuffer::pair();
+
+        let write = LocoClient {
+            current_id: self.current_id,
+            bytes_flushed_total: self.bytes_flushed_total,
+
+            sink: mem::take(&mut self.sink),
+            frames: mem::take(&mut self.frames),
+            frame_written: mem::take(&mut self.frame_written),
+            priority_sink: mem::take(&mut self.priority_sink),
+            zero_writes: mem::take(&mut self.zero_writes),
+
+            stream: LocoStream::new(),
+            parsed_header: None,
+
+            read_state: ReadState::Pending,
+            borrowed_len: 0,
+            borrowed_command: None,
+
+            config: self.config.clone(),
+            flush_window: mem::replace(
+                &mut self.flush_window,
+                FlushWindow {
+                    deadline: None,
+                    sleep: None,
+                },
+            ),
+            read_idle: ReadIdle {
+                deadline: None,
+                sleep: None,
+            },
+
+            stats: self.stats.clone(),
+            activity: self.activity.clone(),
+            shared_buffer: Some(write_buffer),
+            replay: self.replay.take(),
+            injected: VecDeque::new(),
+            read_into: None,
+
+            inner: self.inner.clone(),
+        };
+        self.shared_buffer = Some(read_buffer);
+
+        publish_buffered(
+            &mut self.shared_buffer,
+            &self.stream,
+            &self.sink,
+            &self.priority_sink,
+        );
+
+        (
+            LocoReadHalf {
+                half: HalfGuard {
+                    shutdown: shutdown.clone(),
+                    write: false,
+                },
+                pongs: pongs.clone(),
+                client: self,
+            },
+            LocoWriteHalf {
+                half: HalfGuard {
+                    shutdown: shutdown.clone(),
+                    write: true,
+                },
+                pongs,
+                client: write,
+            },
+            Shutdown { state: shutdown },
+        )
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[derive(Debug)]
+    pub struct LocoReadHalf<T: Clone> {
+        half: HalfGuard,
+        pongs: Arc<Mutex<PongQueue>>,
+
+        #[pin]
+        client: LocoClient<T>,
+    }
+}
+
+impl<T: Clone> LocoReadHalf<T> {
+    pub fn is_pair_of(&self, write: &LocoWriteHalf<T>) -> bool {
+        Arc::ptr_eq(&self.half.shutdown, &write.half.shutdown)
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.half.shutdown.lock().unwrap().requested
+    }
+
+    /// Context shared with the other half, see [`LocoClient::context`]
+    pub fn context<C: Any>(&self) -> Option<&C> {
+        self.client.context()
+    }
+
+    pub fn reunite(self, write: LocoWriteHalf<T>) -> Result<LocoClient<T>, ReuniteError<T>> {
+        reunite(self, write)
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Clone> LocoReadHalf<T> {
+    pub async fn read(&mut self) -> io::Result<BoxedCommand>
+    where
+        T: Unpin,
+    {
+        let mut this = Pin::new(self);
+
+        poll_fn(|cx| this.as_mut().poll_read(cx)).await
+    }
+
+    /// Also writes queued automatic pong replies while the write half is idle.
+    /// A failed pong write is only returned when no command is ready.
+    ///
+    /// Once [`Shutdown::shutdown`] closed the write half and every outstanding command was
+    /// answered, reads fail with [`LocoError::Closed`] instead of waiting for more.
+    pub fn poll_read(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<BoxedCommand>> {
+        let mut this = self.project();
+
+        let mut poll = this.client.as_mut().poll_read(cx);
+        match &poll {
+            Poll::Ready(Ok(command)) => this.half.answered(command.header.id),
+
+            Poll::Pending if this.half.drained(cx) => {
+                *this.client.as_mut().project().read_state = ReadState::Closed;
+                poll = Poll::Ready(Err(LocoError::Closed.into()));
+            }
+
+            _ => {}
+        }
+
+        if matches!(poll, Poll::Ready(Err(_)))
+            && matches!(this.client.read_state, ReadState::Done | ReadState::Closed)
+        {
+            this.half.finish();
+        }
+
+        match poll_write_pongs(this.pongs, this.client, cx) {
+            Err(err) if poll.is_pending() => Poll::Ready(Err(err)),
+            _ => poll,
+        }
+    }
+}
+
+/// Move pongs queued by the read client to the shared queue, writing them out while the
+/// write half is at a command boundary
+fn poll_write_pongs<T: AsyncWrite + Clone>(
+    pongs: &Mutex<PongQueue>,
+    client: Pin<&mut LocoClient<T>>,
+    cx: &mut Context,
+) -> io::Result<()> {
+    let mut pongs = pongs.lock().unwrap();
+    let mut client = client.project();
+
+    pongs
+        .bytes
+        .extend(client.priority_sink.write_buffer.drain(..));
+
+    while pongs.writer_idle && !pongs.bytes.is_empty() {
+        match client
+            .inner
+            .as_mut()
+            .poll_write(cx, pongs.bytes.as_slices().0)
+        {
+            Poll::Ready(Ok(0)) => return Err(ErrorKind::WriteZero.into()),
+
+            Poll::Ready(Ok(written)) => {
+                pongs.bytes.drain(..written);
+            }
+
+            Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {
+                cx.waker().wake_by_ref();
+                break;
+            }
+
+            Poll::Ready(Err(err)) => return Err(err),
+
+            Poll::Pending => break,
+        }
+    }
+
+    Ok(())
+}
+
+impl<T: AsyncRead + AsyncWrite + Clone> Stream for LocoReadHalf<T> {
+    type Item = io::Result<BoxedCommand>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if matches!(self.client.read_state, ReadState::Done | ReadState::Closed) {
+            return Poll::Ready(None);
+        }
+
+        match self.as_mut().poll_read(cx) {
+            Poll::Ready(Err(_)) if matches!(self.client.read_state, ReadState::Closed) => {
+                Poll::Ready(None)
+            }
+
+            // Stream ended cleanly between commands
+            Poll::Ready(Err(err))
+                if err.kind() == ErrorKind::UnexpectedEof
+                    && matches!(self.client.read_state, ReadState::Done)
+                    && LocoError::from_io(&err).is_none() =>
+            {
+                Poll::Ready(None)
+            }
+
+            poll => poll.map(Some),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[derive(Debug)]
+    pub struct LocoWriteHalf<T: Clone> {
+        half: HalfGuard,
+        pongs: Arc<Mutex<PongQueue>>,
+
+        #[pin]
+        client: LocoClient<T>,
+    }
+}
+
+impl<T: Clone> LocoWriteHalf<T> {
+    pub fn is_pair_of(&self, read: &LocoReadHalf<T>) -> bool {
+        read.is_pair_of(self)
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.half.shutdown.lock().unwrap().requested
+    }
+
+    /// Context shared with the other half, see [`LocoClient::context`]
+    pub fn context<C: Any>(&self) -> Option<&C> {
+        self.client.context()
+    }
+
+    pub fn reunite(self, read: LocoReadHalf<T>) -> Result<LocoClient<T>, ReuniteError<T>> {
+        reunite(read, self)
+    }
+}
+
+impl<T: AsyncWrite + Clone> LocoWriteHalf<T> {
+    /// Fails once shutdown is requested, see [`LocoClient::send`]
+    pub async fn send(&mut self, method: Method, data: &[u8]) -> io::Result<u32>
+    where
+        T: Unpin,
+    {
+        self.half.check_shutdown()?;
+
+        take_pongs(&self.pongs, Pin::new(&mut self.client));
+        let mut client = Pin::new(&mut self.client);
+        let id = client.as_mut().write(method, data);
+        self.half.written(id);
+
+        let result = if client.flush_window_due() {
+            poll_fn(|cx| client.as_mut().poll_flush(cx)).await
+        } else {
+            Ok(())
+        };
+        release_pongs(&self.pongs, &self.client);
+
+        result.map(|()| id)
+    }
+
+    /// Fails once shutdown is requested
+    pub fn write(self: Pin<&mut Self>, method: Method, data: &[u8]) -> io::Result<u32> {
+        let this = self.project();
+        this.half.check_shutdown()?;
+
+        let id = this.client.write(method, data);
+        this.half.written(id);
+
+        Ok(id)
+    }
+
+    /// Flushes and closes the write half instead once shutdown is requested
+    pub fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        if ready!(self.as_mut().poll_shutdown(cx))? {
+            return Poll::Ready(Ok(()));
+        }
+
+        let mut this = self.project();
+
+        take_pongs(this.pongs, this.client.as_mut());
+        let poll = this.client.as_mut().poll_flush(cx);
+        release_pongs(this.pongs, &this.client);
+
+        poll
+    }
+
+    pub async fn close(&mut self) -> io::Result<()>
+    where
+        T: Unpin,
+    {
+        take_pongs(&self.pongs, Pin::new(&mut self.client));
+        let result = self.client.close().await;
+        release_pongs(&self.pongs, &self.client);
+
+        result?;
+        self.half.finish();
+
+        Ok(())
+    }
+
+    /// Flush and close the write half if shutdown is requested, returning whether it was.
+    /// Registers to be woken once shutdown is requested otherwise.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<bool>> {
+        let mut this = self.project();
+
+        match this.half.poll_requested(cx) {
+            HalfShutdown::Running => return Poll::Ready(Ok(false)),
+            HalfShutdown::Finished => return Poll::Ready(Ok(true)),
+            HalfShutdown::Requested => {}
+        }
+
+        take_pongs(this.pongs, this.client.as_mut());
+        let poll = this.client.as_mut().poll_close(cx);
+        release_pongs(this.pongs, &this.client);
+
+        ready!(poll)?;
+        this.half.finish();
+
+        Poll::Ready(Ok(true))
+    }
+}
+
+/// Take over pongs queued by the read half before writing, so they go out ahead of the
+/// next command. The read half stops writing until [`release_pongs`].
+fn take_pongs<T: Clone>(pongs: &Mutex<PongQueue>, client: Pin<&mut LocoClient<T>>) {
+    let mut pongs = pongs.lock().unwrap();
+
+    client
+        .project()
+        .priority_sink
+        .write_buffer
+        .extend(pongs.bytes.drain(..));
+    pongs.writer_idle = false;
+}
+
+/// Let the read half write pongs again if no command is partially written
+fn release_pongs<T: Clone>(pongs: &Mutex<PongQueue>, client: &LocoClient<T>) {
+    pongs.lock().unwrap().writer_idle =
+        client.frame_written == 0 && client.priority_sink.write_buffer.is_empty();
+}
+
+impl<T: AsyncWrite + Clone> Sink<(Method, Vec<u8>)> for LocoWriteHalf<T> {
+    type Error = io::Error;
+
+    /// Flushes and closes the write half once shutdown is requested, then fails
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_shutdown(cx))?;
+
+        Poll::Ready(self.half.check_shutdown())
+    }
+
+    fn start_send(self: Pin<&mut Self>, (method, data): (Method, Vec<u8>)) -> io::Result<()> {
+        self.write(method, &data)?;
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        LocoWriteHalf::poll_flush(self, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut this = self.project();
+
+        take_pongs(this.pongs, this.client.as_mut());
+        let poll = this.client.as_mut().poll_close(cx);
+        release_pongs(this.pongs, &this.client);
+
+        ready!(poll)?;
+        this.half.finish();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub fn reunite<T: Clone>(
+    read: LocoReadHalf<T>,
+    write: LocoWriteHalf<T>,
+) -> Result<LocoClient<T>, ReuniteError<T>> {
+    if !read.is_pair_of(&write) {
+        return Err(ReuniteError(Box::new(read), Box::new(write)));
+    }
+
+    let mut client = read.client;
+    let write = write.client;
+
+    client.current_id = write.current_id;
+    client.config = write.config;
+    client.bytes_flushed_total = write.bytes_flushed_total;
+    client.flush_window = write.flush_window;
+    client.stats.reunite_sent(write.stats);
+    client.activity.reunite_write(write.activity);
+    client.shared_buffer = None;
+    client.replay = write.replay;
+
+    client.sink = write.sink;
+    client.frames = write.frames;
+    client.frame_written = write.frame_written;
+    client.zero_writes = write.zero_writes;
+
+    // Automatic pong replies not yet written by either half go after the write half's
+    // priority commands
+    let mut priority_sink = write.priority_sink;
+    priority_sink
+        .write_buffer
+        .extend(mem::take(&mut read.pongs.lock().unwrap().bytes));
+    priority_sink
+        .write_buffer
+        .extend(mem::take(&mut client.priority_sink).write_buffer);
+    client.priority_sink = priority_sink;
+
+    Ok(client)
+}
+
+/// Automatic pong replies moved out of the read half, see [`LocoClient::split`]
+#[derive(Debug)]
+struct PongQueue {
+    bytes: VecDeque<u8>,
+    /// The write half is not writing and has no partially written command
+    writer_idle: bool,
+}
+
+/// Coordinates closing both halves from [`LocoClient::split_with_shutdown`]
+#[derive(Debug)]
+pub struct Shutdown {
+    state: Arc<Mutex<ShutdownState>>,
+}
+
+impl Shutdown {
+    /// Most ids of written commands tracked for [`Self::shutdown`] to wait for answers to
+    pub const MAX_OUTSTANDING: usize = 1024;
+
+    /// Shut both halves down, resolving once both finished.
+    ///
+    /// Writing and sending on the write half fail from now on. The write half flushes its
+    /// buffered commands and closes the inner writer on its next flush, readiness or close
+    /// poll. The read half keeps reading until the write half closed and every command among
+    /// the last [`Self::MAX_OUTSTANDING`] written was answered, then its reads end as with
+    /// a [`crate::close::CloseSignal`]. Commands never answered keep it reading until
+    /// the stream ends.
+    ///
+    /// Both halves are woken to act on it. Dropping a half also finishes it.
+    pub async fn shutdown(self) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.requested = true;
+
+            [state.write_waker.take(), state.read_waker.take()]
+        };
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+
+            if state.write_done && state.read_done {
+                Poll::Ready(())
+            } else {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    requested: bool,
+    write_done: bool,
+    read_done: bool,
+    waker: Option<Waker>,
+
+    /// Ids of written commands not answered yet, oldest first
+    outstanding: VecDeque<u32>,
+    write_waker: Option<Waker>,
+    read_waker: Option<Waker>,
+}
+
+/// Shutdown progress of the write half, see [`HalfGuard::poll_requested`]
+enum HalfShutdown {
+    Running,
+    Requested,
+    Finished,
+}
+
+/// Shared by both halves, identifying the pair and reporting to [`Shutdown`]
+#[derive(Debug)]
+struct HalfGuard {
+    shutdown: Arc<Mutex<ShutdownState>>,
+    write: bool,
+}
+
+impl HalfGuard {
+    fn check_shutdown(&self) -> io::Result<()> {
+        if self.shutdown.lock().unwrap().requested {
+            Err(io::Error::new(
+                ErrorKind::BrokenPipe,
+                "connection is shutting down",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Track a command written by the write half, until the read half reads its answer
+    fn written(&self, id: u32) {
+        let mut state = self.shutdown.lock().unwrap();
+
+        if state.outstanding.len() == Shutdown::MAX_OUTSTANDING {
+            state.outstanding.pop_front();
+        }
+        state.outstanding.push_back(id);
+    }
+
+    fn answered(&self, id: u32) {
+        let mut state = self.shutdown.lock().unwrap();
+
+        if let Some(index) = state.outstanding.iter().position(|&written| written == id) {
+            state.outstanding.remove(index);
+        }
+    }
+
+    /// Shutdown progress for the write half, registering to be woken once requested
+    fn poll_requested(&self, cx: &mut Context) -> HalfShutdown {
+        let mut state = self.shutdown.lock().unwrap();
+
+        if state.write_done {
+            HalfShutdown::Finished
+        } else if state.requested {
+            HalfShutdown::Requested
+        } else {
+            state.write_waker = Some(cx.waker().clone());
+            HalfShutdown::Running
+        }
+    }
+
+    /// Check if the read half has nothing left to wait for, registering to be woken
+    /// once that may change otherwise
+    fn drained(&self, cx: &mut Context) -> bool {
+        let mut state = self.shutdown.lock().unwrap();
+
+        if state.requested && state.write_done && state.outstanding.is_empty() {
+            true
+        } else {
+            state.read_waker = Some(cx.waker().clone());
+            false
+        }
+    }
+
+    fn finish(&self) {
+        let mut state = self.shutdown.lock().unwrap();
+
+        if self.write {
+            state.write_done = true;
+
+            if let Some(waker) = state.read_waker.take() {
+                waker.wake();
+            }
+        } else {
+            state.read_done = true;
+        }
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for HalfGuard {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Halves passed to [`reunite`] did not come from the same [`LocoClient::split`]
+pub struct ReuniteError<T: Clone>(pub Box<LocoReadHalf<T>>, pub Box<LocoWriteHalf<T>>);
+
+impl<T: Clone + Debug> Debug for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone> Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves that are not from the same client")
+    }
+}
+
+impl<T: Clone + Debug> Error for ReuniteError<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        io::ErrorKind,
+        pin::{pin, Pin},
+        task::{Context, Poll},
+    };
+
+    use futures_core::Stream;
+    use loco_protocol::command::Method;
+
+    use crate::{
+        close::CloseSignal,
+        error::LocoError,
+        frame::encode_command,
+        test_util::{block_on, noop_waker, ReadStep, ScriptedTransport},
+        LocoClient, HEADER_SIZE,
+    };
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    #[test]
+    fn read_half_writes_pongs_while_write_half_is_idle() {
+        let ping = encode_command(7, method("PING"), b"");
+        let mut inbound = ping.clone();
+        inbound.extend(encode_command(8, method("MSG"), b"data"));
+
+        let transport = ScriptedTransport::from_chunks([inbound]);
+        let (mut read, mut write) = LocoClient::new(transport.clone())
+            .with_auto_pong(true)
+            .split();
+
+        // Buffered but unflushed, so the write half is still at a command boundary
+        let id = Pin::new(&mut write).write(method("MSG"), b"out").unwrap();
+
+        let command = block_on(read.read()).unwrap();
+        assert_eq!(command.header.id, 8);
+        assert_eq!(transport.written(), ping);
+
+        block_on(std::future::poll_fn(|cx| {
+            Pin::new(&mut write).poll_flush(cx)
+        }))
+        .unwrap();
+
+        let mut expected = ping;
+        expected.extend(encode_command(id, method("MSG"), b"out"));
+        assert_eq!(transport.written(), expected);
+    }
+
+    #[test]
+    fn read_half_limit_counts_write_half_buffer() {
+        let transport =
+            ScriptedTransport::from_chunks([encode_command(1, method("MSG"), &[0; 64])]);
+        let (mut read, mut write) = LocoClient::new(transport)
+            .with_max_total_buffer(100)
+            .split();
+
+        // Unflushed, and only accounted to the write half
+        Pin::new(&mut write).write(method("MSG"), &[0; 60]).unwrap();
+
+        let err = block_on(read.read()).unwrap_err();
+        assert!(matches!(
+            LocoError::from_io(&err),
+            Some(LocoError::BufferLimitExceeded { limit: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn writes_fail_once_shutdown_is_requested() {
+        let (read, mut write, shutdown) =
+            LocoClient::new(ScriptedTransport::default()).split_with_shutdown();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut waiting = pin!(shutdown.shutdown());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        let err = Pin::new(&mut write)
+            .write(method("MSG"), b"late")
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+
+        // Dropping the halves finishes them too
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        drop((read, write));
+        assert!(waiting.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn read_half_stream_ends_on_close() {
+        let command = encode_command(1, method("MSG"), b"body");
+        let transport = ScriptedTransport::new([
+            ReadStep::Data(command[..HEADER_SIZE + 2].to_vec()),
+            ReadStep::Pending,
+        ]);
+        let signal = CloseSignal::new();
+        let (mut read, _write) = LocoClient::new(transport)
+            .with_close_signal(signal.clone())
+            .split();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut read).poll_next(&mut cx).is_pending());
+
+        signal.close();
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+
+    #[test]
+    fn read_half_stream_ends_at_clean_eof() {
+        let transport = ScriptedTransport::from_chunks([encode_command(1, method("MSG"), b"")]);
+        let (mut read, _write) = LocoClient::new(transport).split();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(Some(Ok(_)))
+        ));
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+
+    #[test]
+    fn shutdown_flushes_closes_and_drains_both_halves() {
+        let transport = ScriptedTransport::new([
+            ReadStep::Pending,
+            ReadStep::Data(encode_command(1, method("REQ"), b"answer")),
+            ReadStep::Pending,
+            ReadStep::Pending,
+            ReadStep::Pending,
+        ]);
+        let (mut read, mut write, shutdown) =
+            LocoClient::new(transport.clone()).split_with_shutdown();
+        assert_eq!(Pin::new(&mut write).write(method("REQ"), b"").unwrap(), 1);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut waiting = pin!(shutdown.shutdown());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        // The read half waits for the write half even once every command was answered
+        assert!(Pin::new(&mut read).poll_next(&mut cx).is_pending());
+        let Poll::Ready(Some(Ok(response))) = Pin::new(&mut read).poll_next(&mut cx) else {
+            panic!("response was not read");
+        };
+        assert_eq!(&*response.data, b"answer");
+        assert!(Pin::new(&mut read).poll_next(&mut cx).is_pending());
+
+        // Polling the write half as a forwarding task would flushes and closes it
+        assert!(transport.written().is_empty());
+        assert!(matches!(
+            Pin::new(&mut write).poll_flush(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(transport.written(), encode_command(1, method("REQ"), b""));
+        assert!(transport.is_closed());
+
+        assert!(matches!(
+            Pin::new(&mut read).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+        assert!(transport.remaining_steps() > 0);
+        assert!(waiting.as_mut().poll(&mut cx).is_ready());
+    }
+}
@@ -0,0 +1,148 @@
+/// Per method command counters, collected with the `stats` feature.
+/// Without it this is a zero sized no-op.
+#[derive(Debug, Clone, Default)]
+pub struct LocoStats {
+    #[cfg(feature = "stats")]
+    sent: Vec<(Box<str>, u64)>,
+
+    #[cfg(feature = "stats")]
+    received: Vec<(Box<str>, u64)>,
+
+    #[cfg(feature = "stats")]
+    writes: WriteCounters,
+}
+
+/// Inner writes spent on commands of the normal queue, priority commands are not counted
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default)]
+struct WriteCounters {
+    inner_writes: u64,
+    commands_written: u64,
+    fragmented_commands: u64,
+
+    /// Inner writes which carried bytes of the command at the front so far
+    front_writes: u64,
+}
+
+impl LocoStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            #[cfg(feature = "stats")]
+            sent: Vec::new(),
+            #[cfg(feature = "stats")]
+            received: Vec::new(),
+            #[cfg(feature = "stats")]
+            writes: WriteCounters {
+                inner_writes: 0,
+                commands_written: 0,
+                fragmented_commands: 0,
+                front_writes: 0,
+            },
+        }
+    }
+
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    pub(crate) fn record_sent(&mut self, method: &str) {
+        #[cfg(feature = "stats")]
+        increment(&mut self.sent, method);
+    }
+
+    /// Take send counters from the write half of a split client
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    pub(crate) fn reunite_sent(&mut self, write: LocoStats) {
+        #[cfg(feature = "stats")]
+        {
+            self.sent = write.sent;
+            self.writes = write.writes;
+        }
+    }
+
+    /// Count an inner write of the normal queue which completed `completed` commands,
+    /// leaving the next one `partial` written
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    pub(crate) fn record_inner_write(&mut self, completed: usize, partial: bool) {
+        #[cfg(feature = "stats")]
+        {
+            let writes = &mut self.writes;
+
+            writes.inner_writes += 1;
+            writes.front_writes += 1;
+
+            if completed > 0 {
+                writes.commands_written += completed as u64;
+                if writes.front_writes > Self::FRAGMENTED_WRITES {
+                    writes.fragmented_commands += 1;
+                }
+
+                writes.front_writes = u64::from(partial);
+            }
+        }
+    }
+
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    pub(crate) fn record_received(&mut self, method: &str) {
+        #[cfg(feature = "stats")]
+        increment(&mut self.received, method);
+    }
+}
+
+#[cfg(feature = "stats")]
+impl LocoStats {
+    /// Commands taking more inner writes than this count as fragmented
+    pub const FRAGMENTED_WRITES: u64 = 4;
+
+    pub fn sent(&self, method: &str) -> u64 {
+        count(&self.sent, method)
+    }
+
+    pub fn received(&self, method: &str) -> u64 {
+        count(&self.received, method)
+    }
+
+    pub fn sent_iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.sent.iter().map(|(method, count)| (&**method, *count))
+    }
+
+    pub fn received_iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.received
+            .iter()
+            .map(|(method, count)| (&**method, *count))
+    }
+
+    /// Inner `poll_write` calls which accepted bytes of written commands
+    pub const fn inner_writes(&self) -> u64 {
+        self.writes.inner_writes
+    }
+
+    pub const fn commands_written(&self) -> u64 {
+        self.writes.commands_written
+    }
+
+    /// Commands which took more than [`Self::FRAGMENTED_WRITES`] inner writes. Growing along with
+    /// [`Self::commands_written`] hints at a transport with tiny socket buffers.
+    pub const fn fragmented_commands(&self) -> u64 {
+        self.writes.fragmented_commands
+    }
+
+    pub fn reset(&mut self) {
+        self.sent.clear();
+        self.received.clear();
+        self.writes = WriteCounters::default();
+    }
+}
+
+#[cfg(feature = "stats")]
+fn count(counters: &[(Box<str>, u64)], method: &str) -> u64 {
+    counters
+        .iter()
+        .find(|(name, _)| &**name == method)
+        .map_or(0, |(_, count)| *count)
+}
+
+#[cfg(feature = "stats")]
+fn increment(counters: &mut Vec<(Box<str>, u64)>, method: &str) {
+    match counters.iter_mut().find(|(name, _)| &**name == method) {
+        Some((_, count)) => *count += 1,
+        None => counters.push((method.into(), 1)),
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::{self, Display};
+
+use loco_protocol::command::Command;
+
+use crate::{error::LocoError, RawCommand};
+
+/// Typed view of [`Header::status`](loco_protocol::command::Header::status),
+/// which carries a signed code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Status(pub u16);
+
+impl Status {
+    pub const SUCCESS: Self = Self::from_code(0);
+    pub const INVALID_USER: Self = Self::from_code(-1);
+    pub const CLIENT_ERROR: Self = Self::from_code(-200);
+    pub const NOT_LOGON: Self = Self::from_code(-201);
+    pub const INVALID_METHOD: Self = Self::from_code(-202);
+    pub const INVALID_PARAMETER: Self = Self::from_code(-203);
+    pub const OPERATION_DENIED: Self = Self::from_code(-500);
+
+    pub const fn from_code(code: i16) -> Self {
+        Self(code as u16)
+    }
+
+    pub const fn code(self) -> i16 {
+        self.0 as i16
+    }
+
+    pub const fn is_success(self) -> bool {
+        self.0 == Self::SUCCESS.0
+    }
+
+    /// [`LocoError::Status`] unless this is [`Status::SUCCESS`]
+    pub const fn as_error(self) -> Option<LocoError> {
+        if self.is_success() {
+            None
+        } else {
+            Some(LocoError::Status(self))
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl From<u16> for Status {
+    fn from(status: u16) -> Self {
+        Self(status)
+    }
+}
+
+impl From<Status> for u16 {
+    fn from(status: Status) -> Self {
+        status.0
+    }
+}
+
+/// Typed status of a received command
+pub trait CommandStatus {
+    fn status(&self) -> Status;
+}
+
+impl<T: ?Sized> CommandStatus for Command<T> {
+    fn status(&self) -> Status {
+        Status(self.header.status)
+    }
+}
+
+impl CommandStatus for RawCommand {
+    fn status(&self) -> Status {
+        Status(self.header().status)
+    }
+}
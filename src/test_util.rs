@@ -0,0 +1,307 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+use loco_protocol::command::BoxedCommand;
+
+use crate::LocoClient;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadStep {
+    /// Bytes returned by following reads, split across reads if the buffer is smaller
+    Data(Vec<u8>),
+
+    /// Return `Poll::Pending` once, waking the task immediately
+    Pending,
+
+    /// Fail a single read with the given error kind
+    Error(ErrorKind),
+}
+
+/// In-memory transport which replays scripted inbound steps and captures written bytes.
+///
+/// Clones share the same script and capture buffer. Reads return EOF once the script is exhausted.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedTransport {
+    state: Arc<Mutex<ScriptState>>,
+}
+
+#[derive(Debug, Default)]
+struct ScriptState {
+    inbound: VecDeque<ReadStep>,
+    written: Vec<u8>,
+    reads: usize,
+    flushes: usize,
+    closed: bool,
+}
+
+impl ScriptedTransport {
+    pub fn new(steps: impl IntoIterator<Item = ReadStep>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ScriptState {
+                inbound: steps.into_iter().collect(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Transport returning each chunk from its own reads.
+    ///
+    /// Byte stream clients reassemble commands split anywhere, while message oriented ones
+    /// reject chunks ending inside a header:
+    ///
+    /// ```
+    /// # use futures_loco_protocol::{error::LocoError, frame::encode_command, test_util::ScriptedTransport, LocoClient};
+    /// # use loco_protocol::command::Method;
+    /// let command = encode_command(1, Method::new("MSG").unwrap(), b"data");
+    /// let fragmented = || ScriptedTransport::from_chunks([&command[..10], &command[10..]]);
+    ///
+    /// let reads = LocoClient::new(fragmented()).pump_reads();
+    /// assert_eq!(&*reads[0].as_ref().unwrap().data, b"data");
+    ///
+    /// let reads = LocoClient::new(fragmented())
+    ///     .with_message_oriented(true)
+    ///     .pump_reads();
+    /// let err = reads[0].as_ref().unwrap_err();
+    /// assert!(matches!(LocoError::from_io(err), Some(LocoError::SplitHeader { received: 10 })));
+    ///
+    /// let reads = LocoClient::new(ScriptedTransport::from_chunks([&command[..22], &command[22..]]))
+    ///     .with_message_oriented(true)
+    ///     .pump_reads();
+    /// assert_eq!(&*reads[0].as_ref().unwrap().data, b"data");
+    /// ```
+    pub fn from_chunks<I: IntoIterator>(chunks: I) -> Self
+    where
+        I::Item: Into<Vec<u8>>,
+    {
+        Self::new(chunks.into_iter().map(|chunk| ReadStep::Data(chunk.into())))
+    }
+
+    pub fn push(&self, step: ReadStep) {
+        self.state.lock().unwrap().inbound.push_back(step);
+    }
+
+    pub fn remaining_steps(&self) -> usize {
+        self.state.lock().unwrap().inbound.len()
+    }
+
+    pub fn written(&self) -> Vec<u8> {
+        self.state.lock().unwrap().written.clone()
+    }
+
+    pub fn take_written(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.lock().unwrap().written)
+    }
+
+    /// Number of `poll_read` calls so far
+    pub fn reads(&self) -> usize {
+        self.state.lock().unwrap().reads
+    }
+
+    pub fn flushes(&self) -> usize {
+        self.state.lock().unwrap().flushes
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+impl AsyncRead for ScriptedTransport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        state.reads += 1;
+
+        match state.inbound.pop_front() {
+            Some(ReadStep::Data(mut data)) => {
+                let read = data.len().min(buf.len());
+                buf[..read].copy_from_slice(&data[..read]);
+
+                if read < data.len() {
+                    data.drain(..read);
+                    state.inbound.push_front(ReadStep::Data(data));
+                }
+
+                Poll::Ready(Ok(read))
+            }
+
+            Some(ReadStep::Pending) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+
+            Some(ReadStep::Error(kind)) => Poll::Ready(Err(kind.into())),
+
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+}
+
+impl AsyncWrite for ScriptedTransport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+        }
+
+        state.written.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.state.lock().unwrap().flushes += 1;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.state.lock().unwrap().closed = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+pub(crate) fn noop_waker() -> Waker {
+    Waker::from(Arc::new(NoopWaker))
+}
+
+/// Poll `future` with a no-op waker until it completes, panicking if it stays pending
+#[cfg(test)]
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    const MAX_POLLS: usize = 10_000;
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+
+    for _ in 0..MAX_POLLS {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+
+    panic!("future still pending after {MAX_POLLS} polls");
+}
+
+impl<T: AsyncWrite + Unpin + Clone> LocoClient<T> {
+    /// Most polls [`Self::pump_writes`] makes before giving up on a pending flush
+    pub const MAX_PUMP_POLLS: usize = 10_000;
+
+    /// Poll [`LocoClient::poll_flush`] with a no-op waker until it is ready, without an async
+    /// runtime. Returns `Pending` if it is still pending after [`Self::MAX_PUMP_POLLS`] polls.
+    pub fn pump_writes(&mut self) -> Poll<io::Result<()>> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        for _ in 0..Self::MAX_PUMP_POLLS {
+            if let Poll::Ready(flushed) = Pin::new(&mut *self).poll_flush(&mut cx) {
+                return Poll::Ready(flushed);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T: Clone> LocoClient<T> {
+    /// Queue `command` to be returned by [`LocoClient::poll_read`] as is, bypassing the parser.
+    ///
+    /// Injected commands are returned in the order they were injected, each by the next read
+    /// after its injection, ahead of commands still in the read buffer. Real commands are
+    /// returned once no injected command is left.
+    pub fn inject_command(&mut self, command: BoxedCommand) {
+        self.injected.push_back(command);
+    }
+}
+
+impl<T: AsyncRead + Unpin + Clone> LocoClient<T> {
+    /// Poll [`LocoClient::poll_read`] with a no-op waker until it returns `Pending` or an error,
+    /// without an async runtime. An error is returned as the last item.
+    pub fn pump_reads(&mut self) -> Vec<io::Result<BoxedCommand>> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut reads = Vec::new();
+        while let Poll::Ready(read) = Pin::new(&mut *self).poll_read(&mut cx) {
+            let failed = read.is_err();
+            reads.push(read);
+
+            if failed {
+                break;
+            }
+        }
+
+        reads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loco_protocol::command::Method;
+
+    use super::*;
+    use crate::frame::encode_command;
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    #[test]
+    fn pump_writes_flushes_every_queued_byte() {
+        let transport = ScriptedTransport::default();
+        let mut client = LocoClient::new(transport.clone()).with_max_write_chunk(5);
+        Pin::new(&mut client).write(method("MSG"), b"first");
+        Pin::new(&mut client).write(method("PING"), b"");
+
+        assert!(matches!(client.pump_writes(), Poll::Ready(Ok(()))));
+
+        let mut expected = encode_command(1, method("MSG"), b"first");
+        expected.extend(encode_command(2, method("PING"), b""));
+        assert_eq!(transport.written(), expected);
+        assert_eq!(transport.flushes(), 1);
+    }
+
+    #[test]
+    fn pump_reads_stops_at_pending_or_error() {
+        let first = encode_command(1, method("MSG"), b"first");
+        let second = encode_command(2, method("MSG"), b"second");
+        let transport = ScriptedTransport::new([
+            ReadStep::Data(first[..10].to_vec()),
+            ReadStep::Data(first[10..].to_vec()),
+            ReadStep::Pending,
+            ReadStep::Data(second),
+            ReadStep::Error(ErrorKind::ConnectionReset),
+        ]);
+        let mut client = LocoClient::new(transport.clone());
+
+        let reads = client.pump_reads();
+        assert_eq!(reads.len(), 1);
+        assert_eq!(&*reads[0].as_ref().unwrap().data, b"first");
+
+        let reads = client.pump_reads();
+        assert_eq!(reads.len(), 2);
+        assert_eq!(&*reads[0].as_ref().unwrap().data, b"second");
+        assert_eq!(
+            reads[1].as_ref().unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+        assert_eq!(transport.remaining_steps(), 0);
+    }
+}
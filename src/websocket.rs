@@ -0,0 +1,271 @@
+use std::{
+    error::Error,
+    io, mem,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_sink::Sink;
+
+use crate::HEADER_SIZE;
+
+pin_project_lite::pin_project! {
+    /// Byte stream over a message transport such as WebSocket, where each message
+    /// carries exactly one command.
+    ///
+    /// Written bytes are regrouped into whole commands before being sent as messages,
+    /// incomplete commands stay buffered until the rest is written.
+    ///
+    /// Not `Clone`, as clones would duplicate the partially read message and buffered writes.
+    #[derive(Debug)]
+    pub struct WebSocketStream<S> {
+        read_message: Vec<u8>,
+        read_position: usize,
+
+        write_buffer: Vec<u8>,
+
+        #[pin]
+        inner: S,
+    }
+}
+
+impl<S> WebSocketStream<S> {
+    pub const fn new(inner: S) -> Self {
+        Self {
+            read_message: Vec::new(),
+            read_position: 0,
+
+            write_buffer: Vec::new(),
+
+            inner,
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>>> AsyncRead for WebSocketStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+
+        while *this.read_position == this.read_message.len() {
+            match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(message) => {
+                    *this.read_message = message;
+                    *this.read_position = 0;
+                }
+
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+
+        let remaining = &this.read_message[*this.read_position..];
+        let read = remaining.len().min(buf.len());
+
+        buf[..read].copy_from_slice(&remaining[..read]);
+        *this.read_position += read;
+
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<S, E> WebSocketStream<S>
+where
+    S: Sink<Vec<u8>, Error = E>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    /// Send every complete command in the write buffer as its own message
+    fn poll_send_commands(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        while let Some(len) = command_len(this.write_buffer) {
+            ready!(this.inner.as_mut().poll_ready(cx)).map_err(sink_error)?;
+
+            let rest = this.write_buffer.split_off(len);
+            let message = mem::replace(this.write_buffer, rest);
+
+            this.inner
+                .as_mut()
+                .start_send(message)
+                .map_err(sink_error)?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S, E> AsyncWrite for WebSocketStream<S>
+where
+    S: Sink<Vec<u8>, Error = E>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.as_mut().poll_send_commands(cx))?;
+
+        self.project().write_buffer.extend_from_slice(buf);
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_send_commands(cx))?;
+
+        self.project().inner.poll_flush(cx).map_err(sink_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_send_commands(cx))?;
+
+        self.project().inner.poll_close(cx).map_err(sink_error)
+    }
+}
+
+/// Length of the command at the start of `buf`, if it is complete
+fn command_len(buf: &[u8]) -> Option<usize> {
+    let data_size = u32::from_le_bytes(buf.get(HEADER_SIZE - 4..HEADER_SIZE)?.try_into().unwrap());
+    let len = HEADER_SIZE + data_size as usize;
+
+    (buf.len() >= len).then_some(len)
+}
+
+fn sink_error(err: impl Into<Box<dyn Error + Send + Sync>>) -> io::Error {
+    io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_core::Stream;
+    use futures_io::{AsyncRead, AsyncWrite};
+    use futures_sink::Sink;
+    use loco_protocol::command::Method;
+
+    use super::WebSocketStream;
+    use crate::{frame::encode_command, test_util::noop_waker};
+
+    /// Message transport yielding `inbound` messages and collecting sent ones
+    #[derive(Debug, Default)]
+    struct Messages {
+        inbound: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Stream for Messages {
+        type Item = Vec<u8>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Vec<u8>>> {
+            Poll::Ready(self.inbound.pop_front())
+        }
+    }
+
+    impl Sink<Vec<u8>> for Messages {
+        type Error = io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, message: Vec<u8>) -> io::Result<()> {
+            self.sent.push(message);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn method(name: &str) -> Method {
+        Method::new(name).unwrap()
+    }
+
+    #[test]
+    fn written_commands_leave_as_one_message_each() {
+        let first = encode_command(1, method("MSG"), b"first");
+        let second = encode_command(2, method("MSG"), b"second");
+        let mut stream = WebSocketStream::new(Messages::default());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut write = |buf: &[u8]| {
+            assert!(matches!(
+                Pin::new(&mut stream).poll_write(&mut cx, buf),
+                Poll::Ready(Ok(len)) if len == buf.len()
+            ));
+        };
+
+        // Split inside the header, then inside the body, with the next command following
+        write(&first[..10]);
+        write(&first[10..25]);
+        let mut rest = first[25..].to_vec();
+        rest.extend(&second[..5]);
+        write(&rest);
+        write(&second[5..]);
+
+        assert!(matches!(
+            Pin::new(&mut stream).poll_flush(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(stream.inner().sent, [first, second]);
+    }
+
+    #[test]
+    fn messages_are_read_in_parts() {
+        let first = encode_command(1, method("MSG"), b"first");
+        let second = encode_command(2, method("MSG"), b"second");
+        let mut stream = WebSocketStream::new(Messages {
+            inbound: [first.clone(), second.clone()].into(),
+            ..Default::default()
+        });
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0; 10];
+        let mut reads = Vec::new();
+        loop {
+            let Poll::Ready(Ok(read)) = Pin::new(&mut stream).poll_read(&mut cx, &mut buf) else {
+                panic!("read failed");
+            };
+            if read == 0 {
+                break;
+            }
+
+            reads.push(buf[..read].to_vec());
+        }
+
+        // No read spans two messages
+        let lens = reads.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(lens, [10, 10, 7, 10, 10, 8]);
+        assert_eq!(reads.concat(), [first, second].concat());
+    }
+}
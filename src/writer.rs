@@ -0,0 +1,241 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_io::AsyncWrite;
+use loco_protocol::command::{client::RawHeader, Command, Header, Method};
+
+use crate::{
+    encode_raw_header, next_id, publish_buffered, update_backpressure, LocoClient, SystemClock,
+    HEADER_SIZE,
+};
+
+/// Command body written in chunks straight into the write buffer, see [`LocoClient::begin_command`]
+///
+/// A header with a placeholder id and size is queued first, and both are backfilled by
+/// [`Self::finish`]. Nothing is handed to the inner writer until then, so the body still
+/// takes its whole size in the write buffer, but is never copied out of a separate buffer.
+/// Dropping the writer without finishing removes the partial command.
+///
+/// With [`LocoClient::with_compression`] set, a body larger than
+/// [`crate::compression::CompressionPolicy::min_size`] is compressed by [`Self::finish`]
+/// once every chunk is buffered, replacing the chunks in the write buffer.
+#[derive(Debug)]
+pub struct CommandWriter<'a, T: Clone> {
+    client: Pin<&'a mut LocoClient<T>>,
+    method: Method,
+
+    /// Offset of the placeholder header in the write buffer
+    start: usize,
+    finished: bool,
+}
+
+impl<T: AsyncWrite + Clone> LocoClient<T> {
+    /// Start a command of `method` with a body written in chunks
+    pub fn begin_command(mut self: Pin<&mut Self>, method: Method) -> CommandWriter<'_, T> {
+        let write_buffer = &mut self.as_mut().project().sink.write_buffer;
+
+        let start = write_buffer.len();
+        write_buffer.extend([0; HEADER_SIZE]);
+
+        CommandWriter {
+            client: self,
+            method,
+
+            start,
+            finished: false,
+        }
+    }
+}
+
+impl<T: Clone> CommandWriter<'_, T> {
+    pub fn write_chunk(&mut self, chunk: &[u8]) {
+        self.client
+            .as_mut()
+            .project()
+            .sink
+            .write_buffer
+            .extend(chunk);
+    }
+
+    /// Body bytes written so far
+    pub fn len(&self) -> usize {
+        self.client.sink.write_buffer.len() - self.start - HEADER_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Assign an id and backfill the header, queueing the command like
+    /// [`LocoClient::write`]. Returns the id.
+    pub fn finish(mut self) -> u32 {
+        let body_start = self.start + HEADER_SIZE;
+        let data_size = self.len();
+        let this = self.client.as_mut().project();
+
+        let mut data_type = 0;
+        if let Some((policy, compressor)) = this
+            .config
+            .compression
+            .as_ref()
+            .filter(|(policy, _)| data_size > policy.min_size)
+        {
+            let body = this
+                .sink
+                .write_buffer
+                .range(body_start..)
+                .copied()
+                .collect::<Vec<_>>();
+
+            this.sink.write_buffer.truncate(body_start);
+            this.sink.write_buffer.extend(compressor.compress(&body));
+            data_type = policy.data_type;
+        }
+        let data_size = this.sink.write_buffer.len() - body_start;
+
+        let id = next_id(this.current_id, this.config, &self.method);
+        let header = Header {
+            id,
+            status: 0,
+            method: self.method.clone(),
+            data_type,
+        };
+
+        let raw = encode_raw_header(&RawHeader {
+            header: header.clone(),
+            data_size: data_size as u32,
+        });
+        for (target, byte) in this
+            .sink
+            .write_buffer
+            .range_mut(self.start..self.start + HEADER_SIZE)
+            .zip(raw)
+        {
+            *target = byte;
+        }
+
+        if let (Some((window, _)), None) = (this.config.flush_window, this.flush_window.deadline) {
+            let clock = this.config.clock.as_deref().unwrap_or(&SystemClock);
+            this.flush_window.deadline = Some(clock.now() + window);
+        }
+
+        this.stats.record_sent(&self.method);
+        this.activity.record_queued(
+            this.config.clock(),
+            self.start + this.priority_sink.write_buffer.len(),
+        );
+
+        if let Some(replay) = this.replay {
+            let data = this
+                .sink
+                .write_buffer
+                .range(body_start..)
+                .copied()
+                .collect::<Vec<_>>();
+
+            replay.record(&Command {
+                header,
+                data: &data,
+            });
+        }
+
+        this.frames.push_back(HEADER_SIZE + data_size);
+        update_backpressure(this.config, this.sink, this.priority_sink);
+
+        publish_buffered(
+            this.shared_buffer,
+            this.stream,
+            this.sink,
+            this.priority_sink,
+        );
+
+        self.finished = true;
+        id
+    }
+}
+
+impl<T: Clone> AsyncWrite for CommandWriter<'_, T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write_chunk(buf);
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Does not finish the command, call [`CommandWriter::finish`] afterwards
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Clone> Drop for CommandWriter<'_, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let start = self.start;
+
+            self.client
+                .as_mut()
+                .project()
+                .sink
+                .write_buffer
+                .truncate(start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::Pin, sync::Arc};
+
+    use loco_protocol::command::Method;
+
+    use crate::{
+        compression::{CompressionPolicy, Compressor},
+        frame::decode_command,
+        LocoClient,
+    };
+
+    #[derive(Debug)]
+    struct Reverse;
+
+    impl Compressor for Reverse {
+        fn compress(&self, data: &[u8]) -> Vec<u8> {
+            data.iter().rev().copied().collect()
+        }
+    }
+
+    #[test]
+    fn streamed_bodies_follow_the_compression_policy() {
+        let policy = CompressionPolicy {
+            min_size: 4,
+            data_type: 8,
+        };
+        let mut client = LocoClient::new(Vec::new()).with_compression(policy, Arc::new(Reverse));
+
+        for chunks in [&[&b"ab"[..], b"cd"][..], &[b"ab", b"cd", b"e"]] {
+            let mut writer = Pin::new(&mut client).begin_command(Method::new("MSG").unwrap());
+            for chunk in chunks {
+                writer.write_chunk(chunk);
+            }
+            writer.finish();
+        }
+
+        let written = client.writable_slices().0;
+        let (small, used) = decode_command(written).unwrap().unwrap();
+        let (large, rest) = decode_command(&written[used..]).unwrap().unwrap();
+
+        assert_eq!((small.header.data_type, &*small.data), (0, &b"abcd"[..]));
+        assert_eq!((large.header.data_type, &*large.data), (8, &b"edcba"[..]));
+        assert_eq!(used + rest, written.len());
+    }
+}